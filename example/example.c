@@ -4,6 +4,21 @@
 #include <inttypes.h>
 #include <stdio.h>
 #include <string.h>
+#include <errno.h>
+#include <sys/socket.h>
+#include <poll.h>
+#include <stdbool.h>
+
+
+// waits until a non-blocking fd is ready, returning 0 on success
+static int wait_ready(int fd, short events) {
+    struct pollfd pfd = { .fd = fd, .events = events };
+    int result = 0;
+    do {
+        result = poll(&pfd, 1, -1);
+    } while (0 > result && EINTR == errno);
+    return 0 > result ? -1 : 0;
+}
 
 
 ssize_t read_all(int fd, void * buf, size_t buf_size) {
@@ -14,13 +29,23 @@ ssize_t read_all(int fd, void * buf, size_t buf_size) {
         current_read = read(fd, bytes_buf, buf_size);
         if (0 > current_read)
         {
+            if (EINTR == errno)
+            {
+                // interrupted before any data was read - retry
+                continue;
+            }
+            if ((EAGAIN == errno || EWOULDBLOCK == errno) && 0 == wait_ready(fd, POLLIN))
+            {
+                // non-blocking fd - wait until it's ready and retry
+                continue;
+            }
             perror("read");
             return -1;
         }
         bytes_buf += current_read;
         buf_size -= current_read;
         total_read += current_read;
-    } while (current_read > 0 && buf_size >= 0);
+    } while (current_read != 0 && buf_size >= 0);
     
     if (buf_size < 0)
     {
@@ -42,6 +67,16 @@ ssize_t write_all(int fd, const void * buf, size_t buf_size) {
         current_write = write(fd, bytes_buf, buf_size);
         if (0 > current_write)
         {
+            if (EINTR == errno)
+            {
+                // interrupted before any data was written - retry
+                continue;
+            }
+            if ((EAGAIN == errno || EWOULDBLOCK == errno) && 0 == wait_ready(fd, POLLOUT))
+            {
+                // non-blocking fd - wait until it's ready and retry
+                continue;
+            }
             perror("write");
             return -1;
         }
@@ -52,3 +87,88 @@ ssize_t write_all(int fd, const void * buf, size_t buf_size) {
 
     return total_write;
 }
+
+
+ssize_t recv_all(int sockfd, void * buf, size_t buf_size, int flags) {
+    uint8_t * bytes_buf = buf;
+    ssize_t current_recv = 0;
+    ssize_t total_recv = 0;
+    while (buf_size > 0)
+    {
+        current_recv = recv(sockfd, bytes_buf, buf_size, flags);
+        if (0 > current_recv)
+        {
+            if (EINTR == errno)
+            {
+                continue;
+            }
+            if ((EAGAIN == errno || EWOULDBLOCK == errno) && 0 == wait_ready(sockfd, POLLIN))
+            {
+                // non-blocking fd - wait until it's ready and retry
+                continue;
+            }
+            perror("recv");
+            return -1;
+        }
+        if (0 == current_recv)
+        {
+            fprintf(stderr, "the peer closed the connection!\n");
+            return -1;
+        }
+        bytes_buf += current_recv;
+        buf_size -= current_recv;
+        total_recv += current_recv;
+    }
+
+    return total_recv;
+}
+
+
+ssize_t send_all(int sockfd, const void * buf, size_t buf_size, int flags) {
+    const uint8_t * bytes_buf = buf;
+    ssize_t current_send = 0;
+    ssize_t total_send = 0;
+    while (buf_size > 0)
+    {
+        current_send = send(sockfd, bytes_buf, buf_size, flags | MSG_NOSIGNAL);
+        if (0 > current_send)
+        {
+            if (EINTR == errno)
+            {
+                continue;
+            }
+            if ((EAGAIN == errno || EWOULDBLOCK == errno) && 0 == wait_ready(sockfd, POLLOUT))
+            {
+                // non-blocking fd - wait until it's ready and retry
+                continue;
+            }
+            perror("send");
+            return -1;
+        }
+        bytes_buf += current_send;
+        buf_size -= current_send;
+        total_send += current_send;
+    }
+
+    return total_send;
+}
+
+
+/*
+ * read_all / write_all with the other signatures tester can bind (--read-signature / --write-signature)
+ */
+
+int read_all_status(int fd, void * buf, size_t buf_size, size_t * bytes_read) {
+    ssize_t result = read_all(fd, buf, buf_size);
+    if (0 > result)
+    {
+        return -1;
+    }
+    *bytes_read = result;
+    return 0;
+}
+
+
+bool write_all_bool(int fd, const void * buf, size_t buf_size) {
+    return (ssize_t)buf_size == write_all(fd, buf, buf_size);
+}
@@ -0,0 +1,28 @@
+/*
+ * Fuzzes the `read_all` / `write_all` functions of the library at $TESTIO_FUZZ_LIB - every input is decoded
+ * into a `FuzzCase`, and a case the library fails crashes the target:
+ *
+ *     TESTIO_FUZZ_LIB=$PWD/example/libexample.so cargo +nightly fuzz run read_write
+ */
+
+#![no_main]
+
+use std::path::PathBuf;
+
+use libfuzzer_sys::fuzz_target;
+use testio::fuzz::{FuzzCase, Fuzzer};
+use testio::library::Symbols;
+
+thread_local! {
+    // mounted once, for all of the inputs - the `Fuzzer` isn't `Sync`, and libFuzzer runs the inputs on one thread
+    static FUZZER: Fuzzer = {
+        let library_path = std::env::var_os("TESTIO_FUZZ_LIB").expect("TESTIO_FUZZ_LIB isn't set");
+        Fuzzer::new(&PathBuf::from(library_path), Symbols::default()).expect("Failed to set up the fuzzer")
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(message) = FUZZER.with(|fuzzer| fuzzer.run(&FuzzCase::from_bytes(data))) {
+        panic!("{}", message);
+    }
+});
@@ -0,0 +1,89 @@
+/*
+ * Encodes the data passed between the tester and its caller, so arbitrary bytes (including NUL bytes and invalid UTF-8)
+ * can go through the command line and stdout.
+ */
+
+use clap::ArgEnum;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum Encoding {
+    /// The bytes as they are
+    Raw,
+    /// Two lowercase hex digits per byte
+    Hex,
+    /// Standard base64, padded with '='
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Raw => data.to_vec(),
+            Encoding::Hex => data.iter().flat_map(|byte| format!("{:02x}", byte).into_bytes()).collect(),
+            Encoding::Base64 => encode_base64(data),
+        }
+    }
+
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Raw => Ok(text.as_bytes().to_vec()),
+            Encoding::Hex => decode_hex(text),
+            Encoding::Base64 => decode_base64(text),
+        }
+    }
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err("hex data must have an even amount of digits".to_string());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            text.get(index..index + 2)
+                .filter(|digits| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid hex digits at {}", index))
+        })
+        .collect()
+}
+
+fn encode_base64(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| group | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            encoded.push(match index <= chunk.len() {
+                true => BASE64_ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize],
+                false => b'=',
+            });
+        }
+    }
+    encoded
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim_end_matches('=');
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    let mut group = 0u32;
+    for (index, digit) in text.bytes().enumerate() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|candidate| *candidate == digit)
+            .ok_or_else(|| format!("invalid base64 digit at {}", index))?;
+        group = group << 6 | value as u32;
+        if index % 4 == 3 {
+            decoded.extend_from_slice(&group.to_be_bytes()[1..]);
+            group = 0;
+        }
+    }
+    match text.len() % 4 {
+        0 => {},
+        2 => decoded.push((group >> 4) as u8),
+        3 => decoded.extend_from_slice(&((group >> 2) as u16).to_be_bytes()),
+        _ => return Err("invalid base64 length".to_string()),
+    }
+    Ok(decoded)
+}
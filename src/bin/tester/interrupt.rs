@@ -0,0 +1,75 @@
+/*
+ * Fires timer signals (SIGALRM) while the library runs.
+ * The handler is installed without SA_RESTART, so slow syscalls that are interrupted fail with EINTR instead of restarting.
+ */
+
+use libc::c_int;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn count_interrupt(_signal: c_int) {
+    INTERRUPTS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn set_timer(interval_us: u64) {
+    let interval = libc::timeval {
+        tv_sec: (interval_us / 1_000_000) as libc::time_t,
+        tv_usec: (interval_us % 1_000_000) as libc::suseconds_t,
+    };
+    let timer = libc::itimerval { it_interval: interval, it_value: interval };
+    if unsafe { libc::setitimer(libc::ITIMER_REAL, &timer, std::ptr::null_mut()) } != 0 {
+        panic!("Failed to set the timer: {}", std::io::Error::last_os_error());
+    }
+}
+
+fn start(interval_us: u64) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = count_interrupt as *const () as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGALRM, &action, std::ptr::null_mut()) != 0 {
+            panic!("Failed to install the SIGALRM handler: {}", std::io::Error::last_os_error());
+        }
+    }
+    set_timer(interval_us);
+}
+
+// stops the timer, returning the amount of signals that were fired
+fn stop() -> usize {
+    set_timer(0);
+    INTERRUPTS.load(Ordering::Relaxed)
+}
+
+/// Blocks SIGALRM in the calling thread - for the peer threads, so the signals only interrupt the library
+pub fn block_interrupts() {
+    let result = unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGALRM);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut())
+    };
+    if result != 0 {
+        panic!("Failed to block SIGALRM: {}", std::io::Error::from_raw_os_error(result));
+    }
+}
+
+/// Fires SIGALRM every `interval_us` microseconds (when set) during the library calls
+pub struct Interrupts {
+    pub interval_us: Option<u64>,
+}
+
+impl Interrupts {
+    /// Runs `call` under the timer, printing the amount of signals fired to stderr
+    pub fn during<T>(&self, call: impl FnOnce() -> T) -> T {
+        let interval_us = match self.interval_us {
+            Some(interval_us) => interval_us,
+            None => return call(),
+        };
+        start(interval_us);
+        let result = call();
+        eprintln!("interrupts: {}", stop());
+        result
+    }
+}
@@ -0,0 +1,359 @@
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use libc::size_t;
+use std::{fs::File, ffi::{OsStr, OsString}, os::unix::prelude::{AsRawFd, OpenOptionsExt, RawFd}, io::{Seek, SeekFrom}, path::PathBuf};
+use testio::library::{Library, Signature, Symbols};
+use testio::scenario::Policy;
+
+mod encoding;
+mod interrupt;
+mod measure;
+mod output;
+mod peer;
+mod pipe;
+mod socket;
+
+use encoding::Encoding;
+use interrupt::Interrupts;
+use measure::{measure, Measured};
+use output::Output;
+use peer::Peer;
+
+#[derive(Parser)]
+#[clap(author, version, long_about = None)]
+#[clap(about = "Tests a given r/w wrapper library")]
+struct TesterCli {
+    
+    /// The path to the library
+    library_path: OsString,
+    
+    /// The path to the file to read. With a pipe / socket, the peer sends the content of the file or writes what it receives to it
+    file_path: OsString,
+
+    /// Fires SIGALRM every INTERRUPT_US microseconds while the library runs, handled without SA_RESTART.
+    /// The amount of signals fired is printed to stderr
+    #[clap(long)]
+    interrupt_us: Option<u64>,
+
+    /// Opens the file (or the library's end of the pipe / socket) with O_NONBLOCK
+    #[clap(long)]
+    nonblock: bool,
+
+    /// The name of the read function of the library
+    #[clap(long, default_value = "read_all")]
+    read_symbol: String,
+
+    /// The signature of the read function
+    #[clap(long, arg_enum, default_value = "ssize")]
+    read_signature: Signature,
+
+    /// The name of the write function of the library
+    #[clap(long, default_value = "write_all")]
+    write_symbol: String,
+
+    /// The signature of the write function
+    #[clap(long, arg_enum, default_value = "ssize")]
+    write_signature: Signature,
+
+    /// How the data passed on the command line and the data printed to stdout are encoded
+    #[clap(long, arg_enum, default_value = "raw")]
+    encoding: Encoding,
+
+    /// Writes the data read / received by the library to OUT_FILE (unencoded) instead of printing it
+    #[clap(long)]
+    out_file: Option<PathBuf>,
+
+    /// How the outcome is reported
+    #[clap(long, arg_enum, default_value = "text")]
+    output: Output,
+
+    #[clap(subcommand)]
+    command: Commands
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Tests the read_all functionality of the library
+    Read { 
+        #[clap(long)]
+        #[clap(default_value_t = 0)]
+        /// The offset to start reading from
+        offset: u64,
+        #[clap(long)]
+        /// The count to read. by default, the whole file is read
+        count: Option<usize>,
+        #[clap(long, arg_enum, default_value = "file")]
+        /// What the library reads from - the file, or a pipe a peer writes the content of the file to
+        source: Channel,
+        #[clap(flatten)]
+        peer: PeerArgs,
+    },
+
+    /// Tests the write_all functionality of the library
+    Write {
+        #[clap(flatten)]
+        data: DataArgs,
+        #[clap(long, arg_enum, default_value = "file")]
+        /// What the library writes to - the file, or a pipe a peer drains into the file
+        sink: Channel,
+        #[clap(flatten)]
+        peer: PeerArgs,
+    },
+
+    /// Tests the recv_all functionality of the library - a peer sends it the content of the file over a loopback TCP connection
+    Recv {
+        #[clap(long)]
+        /// The count to receive. by default, the size of the file
+        count: Option<usize>,
+        #[clap(flatten)]
+        peer: PeerArgs,
+    },
+
+    /// Tests the send_all functionality of the library - a peer receives the data over a loopback TCP connection and writes it to the file
+    Send {
+        #[clap(flatten)]
+        data: DataArgs,
+        #[clap(long)]
+        /// Shrinks the send buffer of the library's socket (SO_SNDBUF) to SNDBUF bytes
+        sndbuf: Option<usize>,
+        #[clap(flatten)]
+        peer: PeerArgs,
+    },
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum Channel {
+    File,
+    Pipe,
+}
+
+/// The data the library writes / sends
+#[derive(Args)]
+struct DataArgs {
+    #[clap(required_unless_present = "data-file")]
+    /// The data, encoded with --encoding
+    data: Option<String>,
+    #[clap(long, conflicts_with = "data")]
+    /// Takes the data from DATA_FILE (unencoded) instead
+    data_file: Option<PathBuf>,
+}
+
+impl DataArgs {
+    fn into_bytes(self, encoding: Encoding) -> Vec<u8> {
+        match (self.data, self.data_file) {
+            (_, Some(data_file)) => std::fs::read(data_file).expect("Failed to read the data file"),
+            (Some(data), None) => encoding.decode(&data).unwrap_or_else(|err| panic!("Failed to decode the data: {}", err)),
+            (None, None) => unreachable!("clap requires the data or a data file"),
+        }
+    }
+}
+
+/// The behavior of the peer on the other end of a pipe / socket
+#[derive(Args)]
+struct PeerArgs {
+    #[clap(long, parse(try_from_str = parse_policy))]
+    /// Shapes the size of every write / read call of the peer, a JSON policy like the read / write policies of a scenario
+    /// (e.g. {"kind": "constant", "size": 1})
+    policy: Option<Policy>,
+    #[clap(long)]
+    /// Resets the connection (or closes the pipe) after RESET_AFTER bytes
+    reset_after: Option<usize>,
+}
+
+impl From<PeerArgs> for Peer {
+    fn from(args: PeerArgs) -> Self {
+        Peer { policy: args.policy, abort_after: args.reset_after }
+    }
+}
+
+fn parse_policy(policy: &str) -> Result<Policy, Box<dyn std::error::Error + Send + Sync>> {
+    let policy: Policy = serde_json::from_str(policy)?;
+    policy.validate("--policy")?;
+    Ok(policy)
+}
+
+
+fn open_file(file_path: &OsStr, for_write: bool, nonblock: bool) -> File {
+    let flags = if nonblock { libc::O_NONBLOCK } else { 0 };
+    File::options().read(true).write(for_write).custom_flags(flags).open(file_path).expect("Failed to open file!")
+}
+
+// sets O_NONBLOCK on the library's end of a pipe / socket
+fn set_nonblocking(fd: RawFd, nonblock: bool) {
+    if !nonblock {
+        return;
+    }
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        panic!("Failed to set O_NONBLOCK: {}", std::io::Error::last_os_error());
+    }
+}
+
+// reads `count` bytes from `fd` with the library, returning the data
+fn read_fd(library: &Library, interrupts: &Interrupts, fd: RawFd, count: usize) -> (Measured, Vec<u8>) {
+    let mut buf= Vec::<u8>::with_capacity(count as usize);
+    let buf_ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    let read_all = library.read_all().unwrap_or_else(|err| panic!("{}", err));
+    let measured = measure(interrupts, || read_all.call(fd, buf_ptr, count as size_t));
+    
+    let buffer = unsafe {
+        Vec::from_raw_parts(
+            buf_ptr,
+            measured.bytes(),
+            count)
+    };
+    (measured, buffer)
+}
+
+fn handle_read(library: &Library, interrupts: &Interrupts, mut file: File, offset: u64, count: Option<usize>) -> (Measured, Vec<u8>) {
+    let count = match count {
+        Some(count) => count,
+        None => file.metadata().expect("Failed to calculate file length").len() as usize,
+    };
+
+    if offset != 0
+    {
+        file.seek(SeekFrom::Start(offset as u64)).expect("Failed to set offset");
+    }
+    read_fd(library, interrupts, file.as_raw_fd(), count)
+}
+
+fn handle_read_pipe(
+    library: &Library,
+    interrupts: &Interrupts,
+    file_path: &OsStr,
+    offset: u64,
+    count: Option<usize>,
+    peer: Peer,
+    nonblock: bool,
+) -> (Measured, Vec<u8>) {
+    let data = std::fs::read(file_path).expect("Failed to read the file");
+    let data = data.get(offset as usize..).unwrap_or_default().to_vec();
+    let count = count.unwrap_or(data.len());
+
+    let (read_end, peer) = pipe::source(move |write_end| peer.send(write_end, &data));
+    set_nonblocking(read_end.as_raw_fd(), nonblock);
+    let outcome = read_fd(library, interrupts, read_end.as_raw_fd(), count);
+    // the peer may be blocked on a full pipe if the library stopped early
+    drop(read_end);
+    peer.join().expect("The peer failed");
+    outcome
+}
+
+fn handle_write(library: &Library, interrupts: &Interrupts, file: File, data: Vec<u8>) -> Measured {
+    let size = data.len();
+
+    let write_all = library.write_all().unwrap_or_else(|err| panic!("{}", err));
+    measure(interrupts, || write_all.call(file.as_raw_fd(), data.as_ptr(), size))
+}
+
+fn handle_write_pipe(library: &Library, interrupts: &Interrupts, file_path: &OsStr, data: Vec<u8>, peer: Peer, nonblock: bool) -> Measured {
+    let write_all = library.write_all().unwrap_or_else(|err| panic!("{}", err));
+    let (write_end, peer) = pipe::sink(move |read_end| peer.receive(read_end));
+    set_nonblocking(write_end.as_raw_fd(), nonblock);
+    let measured = measure(interrupts, || write_all.call(write_end.as_raw_fd(), data.as_ptr(), data.len()));
+    drop(write_end);
+
+    let received = peer.join().expect("The peer failed");
+    std::fs::write(file_path, received).expect("Failed to write the received data to the file");
+    measured
+}
+
+fn handle_recv(
+    library: &Library,
+    interrupts: &Interrupts,
+    file_path: &OsStr,
+    count: Option<usize>,
+    peer: Peer,
+    nonblock: bool,
+) -> (Measured, Vec<u8>) {
+    let recv_all = library.recv_all().unwrap_or_else(|err| panic!("{}", err));
+    let data = std::fs::read(file_path).expect("Failed to read the file");
+    let count = count.unwrap_or(data.len());
+
+    let (stream, peer) = socket::connect(move |stream| peer.send(stream, &data));
+    set_nonblocking(stream.as_raw_fd(), nonblock);
+    let mut buffer = vec![0u8; count];
+    let measured = measure(interrupts, || recv_all(stream.as_raw_fd(), buffer.as_mut_ptr(), count, 0));
+    drop(stream);
+    peer.join().expect("The peer failed");
+
+    buffer.truncate(measured.bytes());
+    (measured, buffer)
+}
+
+fn handle_send(
+    library: &Library,
+    interrupts: &Interrupts,
+    file_path: &OsStr,
+    data: Vec<u8>,
+    sndbuf: Option<usize>,
+    peer: Peer,
+    nonblock: bool,
+) -> Measured {
+    let send_all = library.send_all().unwrap_or_else(|err| panic!("{}", err));
+    let (stream, peer) = socket::connect(move |stream| peer.receive(stream));
+    set_nonblocking(stream.as_raw_fd(), nonblock);
+    if let Some(size) = sndbuf {
+        socket::set_send_buffer(&stream, size);
+    }
+    let measured = measure(interrupts, || send_all(stream.as_raw_fd(), data.as_ptr(), data.len(), 0));
+    drop(stream);
+
+    let received = peer.join().expect("The peer failed");
+    std::fs::write(file_path, received).expect("Failed to write the received data to the file");
+    measured
+}
+
+
+fn main() {
+    env_logger::init();
+
+    let cli = TesterCli::parse();
+
+
+    let library_path = &cli.library_path;
+    let file_path = &cli.file_path;
+    
+    let symbols = Symbols {
+        read: cli.read_symbol,
+        read_signature: cli.read_signature,
+        write: cli.write_symbol,
+        write_signature: cli.write_signature,
+    };
+    let library = Library::load(library_path, symbols).unwrap_or_else(|err| panic!("{}", err));
+    let interrupts = Interrupts { interval_us: cli.interrupt_us };
+    let nonblock = cli.nonblock;
+
+    let encoding = cli.encoding;
+
+    let (measured, data) = match cli.command {
+        Commands::Read { offset, count, source: Channel::File, .. } => {
+            let file = open_file(file_path, false, nonblock);
+            let (measured, data) = handle_read(&library, &interrupts, file, offset, count);
+            (measured, Some(data))
+        },
+        Commands::Read { offset, count, source: Channel::Pipe, peer } => {
+            let (measured, data) = handle_read_pipe(&library, &interrupts, file_path, offset, count, peer.into(), nonblock);
+            (measured, Some(data))
+        },
+        Commands::Write { data, sink: Channel::File, .. } => {
+            let file = open_file(file_path, true, nonblock);
+            (handle_write(&library, &interrupts, file, data.into_bytes(encoding)), None)
+        },
+        Commands::Write { data, sink: Channel::Pipe, peer } => {
+            let data = data.into_bytes(encoding);
+            (handle_write_pipe(&library, &interrupts, file_path, data, peer.into(), nonblock), None)
+        },
+        Commands::Recv { count, peer } => {
+            let (measured, data) = handle_recv(&library, &interrupts, file_path, count, peer.into(), nonblock);
+            (measured, Some(data))
+        },
+        Commands::Send { data, sndbuf, peer } => {
+            let data = data.into_bytes(encoding);
+            (handle_send(&library, &interrupts, file_path, data, sndbuf, peer.into(), nonblock), None)
+        },
+    };
+
+    cli.output.report(&measured, data.as_deref(), encoding, cli.out_file.as_deref());
+}
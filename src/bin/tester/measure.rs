@@ -0,0 +1,97 @@
+/*
+ * Measures a single call into the library - its result, errno, what it printed to stderr, duration and the syscalls it made.
+ */
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+use libc::{c_int, ssize_t};
+
+use crate::interrupt::Interrupts;
+
+/// The outcome of a library call
+pub struct Measured {
+    pub result: ssize_t,
+    /// errno right after the call returned (0 if the call didn't set it)
+    pub errno: c_int,
+    /// What the library printed to stderr during the call
+    pub stderr: Vec<u8>,
+    pub elapsed: Duration,
+    /// The amount of read / write syscalls the call made (the read and write families - `recv` / `send` aren't counted by the kernel)
+    pub syscalls: Option<u64>,
+}
+
+impl Measured {
+    /// The amount of bytes transferred by the call
+    pub fn bytes(&self) -> usize {
+        std::cmp::max(self.result, 0) as usize
+    }
+}
+
+// the read / write syscalls made by the calling thread so far, from /proc/thread-self/io
+fn syscalls() -> Option<u64> {
+    let io = std::fs::read_to_string("/proc/thread-self/io").ok()?;
+    io.lines()
+        .filter(|line| line.starts_with("syscr:") || line.starts_with("syscw:"))
+        .map(|line| line.split_whitespace().nth(1).and_then(|count| count.parse::<u64>().ok()))
+        .sum()
+}
+
+// redirects stderr to an in-memory file while the library runs
+struct StderrCapture {
+    saved: RawFd,
+    file: File,
+}
+
+impl StderrCapture {
+    fn start() -> Self {
+        let fd = unsafe { libc::memfd_create(c"tester-stderr".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            panic!("Failed to create the stderr capture file: {}", std::io::Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        let saved = unsafe { libc::dup(libc::STDERR_FILENO) };
+        if saved < 0 || unsafe { libc::dup2(file.as_raw_fd(), libc::STDERR_FILENO) } < 0 {
+            panic!("Failed to redirect stderr: {}", std::io::Error::last_os_error());
+        }
+        Self { saved, file }
+    }
+
+    // restores stderr, returning what was written to it
+    fn finish(mut self) -> Vec<u8> {
+        unsafe {
+            libc::dup2(self.saved, libc::STDERR_FILENO);
+            libc::close(self.saved);
+        }
+        let mut captured = Vec::new();
+        self.file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.file.read_to_end(&mut captured))
+            .expect("Failed to read the captured stderr");
+        captured
+    }
+}
+
+/// Runs `call` under `interrupts`, measuring it
+pub fn measure(interrupts: &Interrupts, call: impl FnOnce() -> ssize_t) -> Measured {
+    // reading /proc/thread-self/io makes syscalls too - measure how many to subtract them
+    let overhead = syscalls().zip(syscalls()).map(|(first, second)| second - first);
+    interrupts.during(|| {
+        let capture = StderrCapture::start();
+        let syscalls_before = syscalls();
+        unsafe { *libc::__errno_location() = 0 };
+        let start = Instant::now();
+        let result = call();
+        // before anything else can touch errno
+        let errno = unsafe { *libc::__errno_location() };
+        let elapsed = start.elapsed();
+        let syscalls = syscalls_before
+            .zip(syscalls())
+            .zip(overhead)
+            .map(|((before, after), overhead)| (after - before).saturating_sub(overhead));
+        let stderr = capture.finish();
+        Measured { result, errno, stderr, elapsed, syscalls }
+    })
+}
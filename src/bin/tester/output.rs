@@ -0,0 +1,68 @@
+/*
+ * Reports the outcome of the tested call - as text (the data, then the result on the last line) or as a single JSON object.
+ */
+
+use std::io::Write;
+use std::path::Path;
+
+use clap::ArgEnum;
+use serde_json::json;
+use testio::checksum::crc32;
+
+use crate::encoding::Encoding;
+use crate::measure::Measured;
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum Output {
+    /// The data (if any), then the result on the last line.
+    /// What the library printed to stderr is passed through, followed by an `errno: N` line
+    Text,
+    /// A single JSON object with the result, errno, bytes transferred, CRC32 of the data read, elapsed time, syscall count
+    /// and what the library printed to stderr
+    Json,
+}
+
+fn print_data(data: &[u8], encoding: Encoding) {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(&encoding.encode(data)).and_then(|_| stdout.write_all(b"\n")).expect("Failed to print data");
+}
+
+impl Output {
+    /// Reports `measured`, along with the data the library read (when it read any).
+    /// With `out_file`, the data is written to it instead of being printed
+    pub fn report(&self, measured: &Measured, data: Option<&[u8]>, encoding: Encoding, out_file: Option<&Path>) {
+        if let (Some(data), Some(out_file)) = (data, out_file) {
+            std::fs::write(out_file, data).expect("Failed to write the data to the out file");
+        }
+        let printed = data.filter(|_| out_file.is_none());
+        match self {
+            Output::Text => {
+                let mut stderr = std::io::stderr();
+                stderr.write_all(&measured.stderr).expect("Failed to print the library's stderr");
+                eprintln!("errno: {}", measured.errno);
+                if let Some(data) = printed {
+                    print_data(data, encoding);
+                }
+                println!("{}", measured.result);
+            },
+            Output::Json => {
+                let mut report = json!({
+                    "result": measured.result,
+                    "errno": measured.errno,
+                    "bytes": measured.bytes(),
+                    "elapsed": measured.elapsed.as_secs_f64(),
+                    "syscalls": measured.syscalls,
+                    "stderr": String::from_utf8_lossy(&measured.stderr),
+                });
+                if let Some(data) = data {
+                    report["crc32"] = json!(crc32(data));
+                }
+                // raw data can't be embedded in JSON
+                if let (Some(data), Encoding::Hex | Encoding::Base64) = (printed, encoding) {
+                    report["data"] = json!(String::from_utf8(encoding.encode(data)).expect("Encoded data is ASCII"));
+                }
+                println!("{}", report);
+            },
+        }
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * The other end of the pipe / socket the library works on.
+ * The peer runs in a helper thread and sends (or receives) the data in fragments shaped by the same policies
+ * as the read / write policies of a scenario.
+ */
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use testio::scenario::Policy;
+
+// the pause between two fragments sent by the peer, so the library receives every fragment separately
+const FRAGMENT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The peer's end of a pipe or a socket
+pub trait Endpoint: Read + Write {
+    /// Closes the connection abruptly - a reset for sockets, or a plain close for pipes
+    fn abort(self);
+}
+
+impl Endpoint for File {
+    fn abort(self) {}
+}
+
+/// How the peer behaves
+pub struct Peer {
+    /// Shapes the size of every `write` / `read` call of the peer, the whole buffer by default
+    pub policy: Option<Policy>,
+    /// Aborts the connection after this many bytes
+    pub abort_after: Option<usize>,
+}
+
+impl Peer {
+    fn fragment_size(&self, call: usize, size: usize) -> usize {
+        match &self.policy {
+            Some(policy) => {
+                let size = std::cmp::min(size, u32::MAX as usize) as u32;
+                std::cmp::max(policy.shape(call, size), 1) as usize
+            },
+            None => size,
+        }
+    }
+
+    /// Sends `data` to the library, then closes the connection
+    pub fn send(&self, mut endpoint: impl Endpoint, data: &[u8]) {
+        let end = self.abort_after.map_or(data.len(), |abort_after| std::cmp::min(abort_after, data.len()));
+        let mut sent = 0;
+        let mut call = 0;
+        while sent < end {
+            let size = self.fragment_size(call, end - sent);
+            // the library may stop receiving early
+            if endpoint.write_all(&data[sent..sent + size]).is_err() {
+                return;
+            }
+            sent += size;
+            call += 1;
+            thread::sleep(FRAGMENT_INTERVAL);
+        }
+        if self.abort_after.is_some() {
+            endpoint.abort();
+        }
+    }
+
+    /// Receives data from the library until it closes the connection, returning the data
+    pub fn receive(&self, mut endpoint: impl Endpoint) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buffer = vec![0; 1 << 16];
+        let mut call = 0;
+        loop {
+            let limit = match self.abort_after {
+                Some(abort_after) if data.len() >= abort_after => {
+                    endpoint.abort();
+                    return data;
+                },
+                Some(abort_after) => std::cmp::min(abort_after - data.len(), buffer.len()),
+                None => buffer.len(),
+            };
+            let size = self.fragment_size(call, limit);
+            match endpoint.read(&mut buffer[..size]) {
+                Ok(0) => return data,
+                Ok(size) => data.extend_from_slice(&buffer[..size]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => panic!("The peer failed to receive: {}", err),
+            }
+            call += 1;
+        }
+    }
+}
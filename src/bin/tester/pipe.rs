@@ -0,0 +1,36 @@
+/*
+ * The pipe harness of read_all / write_all - the library works on one end of a pipe, and a peer thread on the other.
+ */
+
+use std::fs::File;
+use std::os::unix::prelude::FromRawFd;
+use std::thread::{self, JoinHandle};
+
+use crate::interrupt;
+
+/// Creates a pipe, returning its (read end, write end)
+fn pipe() -> (File, File) {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        panic!("Failed to create a pipe: {}", std::io::Error::last_os_error());
+    }
+    unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+}
+
+/// Creates a pipe whose write end is handed to a peer thread running `peer`, returning the read end
+pub fn source<T: Send + 'static>(peer: impl FnOnce(File) -> T + Send + 'static) -> (File, JoinHandle<T>) {
+    let (read_end, write_end) = pipe();
+    (read_end, thread::spawn(move || {
+        interrupt::block_interrupts();
+        peer(write_end)
+    }))
+}
+
+/// Creates a pipe whose read end is handed to a peer thread running `peer`, returning the write end
+pub fn sink<T: Send + 'static>(peer: impl FnOnce(File) -> T + Send + 'static) -> (File, JoinHandle<T>) {
+    let (read_end, write_end) = pipe();
+    (write_end, thread::spawn(move || {
+        interrupt::block_interrupts();
+        peer(read_end)
+    }))
+}
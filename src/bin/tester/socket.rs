@@ -0,0 +1,53 @@
+/*
+ * The socket harness of send_all / recv_all - the library talks to a peer thread over a loopback TCP connection.
+ */
+
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::prelude::AsRawFd;
+use std::thread::{self, JoinHandle};
+
+use libc::c_int;
+
+use crate::interrupt;
+use crate::peer::Endpoint;
+
+fn set_option<T>(stream: &TcpStream, option: c_int, value: T) {
+    let result = unsafe {
+        libc::setsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            &value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        panic!("Failed to set socket option {}: {}", option, std::io::Error::last_os_error());
+    }
+}
+
+impl Endpoint for TcpStream {
+    // closes the connection with a zero linger timeout, which sends RST instead of FIN
+    fn abort(self) {
+        set_option(&self, libc::SO_LINGER, libc::linger { l_onoff: 1, l_linger: 0 });
+    }
+}
+
+/// Shrinks the send buffer of `stream` (SO_SNDBUF), so large sends are split by the kernel
+pub fn set_send_buffer(stream: &TcpStream, size: usize) {
+    set_option(stream, libc::SO_SNDBUF, size as c_int);
+}
+
+/// Connects a socket for the library to a peer thread running `peer`
+pub fn connect<T: Send + 'static>(peer: impl FnOnce(TcpStream) -> T + Send + 'static) -> (TcpStream, JoinHandle<T>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to listen on the loopback interface");
+    let address = listener.local_addr().expect("Failed to get the listening address");
+    let peer = thread::spawn(move || {
+        interrupt::block_interrupts();
+        let (stream, _) = listener.accept().expect("Failed to accept the connection");
+        stream.set_nodelay(true).expect("Failed to disable Nagle's algorithm");
+        peer(stream)
+    });
+    let stream = TcpStream::connect(address).expect("Failed to connect to the peer");
+    (stream, peer)
+}
@@ -0,0 +1,54 @@
+/*
+ * `testio fuzz` - runs a library against fuzz cases (see `testio::fuzz`): the given input files, such as the corpus
+ * or the crashes of an external fuzzer, or inputs generated from a seed.
+ */
+
+use std::path::{Path, PathBuf};
+
+use testio::fuzz::{FuzzCase, Fuzzer};
+use testio::library::Symbols;
+use testio::rng::Rng;
+
+// the largest generated input - the schedule and a few KB of data
+const MAX_INPUT: u64 = 8192;
+
+// the input generated from `seed` - `--seed <seed> --iterations 1` replays it
+fn generate(seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let size = rng.range(0, MAX_INPUT);
+    (0..size).map(|_| rng.next_u64() as u8).collect()
+}
+
+// runs a case, printing it if the library failed it
+fn run_case(fuzzer: &Fuzzer, name: &str, input: &[u8]) -> bool {
+    match fuzzer.run(&FuzzCase::from_bytes(input)) {
+        Ok(()) => true,
+        Err(message) => {
+            println!("{} ... FAILED: {}", name, message);
+            false
+        },
+    }
+}
+
+/*
+ * Runs the cases of `inputs`, or `iterations` cases generated from the seeds `seed..seed + iterations` when there are
+ * no inputs, returning whether the library passed all of them
+ */
+pub fn fuzz(library_path: &Path, symbols: Symbols, inputs: &[PathBuf], seed: u64, iterations: u64) -> bool {
+    let fuzzer = Fuzzer::new(library_path, symbols).expect("Failed to set up the fuzzer");
+    let mut outcomes = Vec::new();
+    for input_path in inputs {
+        let input = std::fs::read(input_path).expect("Failed to read the input");
+        outcomes.push(run_case(&fuzzer, &input_path.to_string_lossy(), &input));
+    }
+    if inputs.is_empty() {
+        for seed in seed..seed.saturating_add(iterations) {
+            outcomes.push(run_case(&fuzzer, &format!("seed {}", seed), &generate(seed)));
+        }
+    }
+    drop(fuzzer);
+
+    let passed = outcomes.iter().filter(|passed| **passed).count();
+    println!("{} passed, {} failed", passed, outcomes.len() - passed);
+    passed == outcomes.len()
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use testio::{harness, testfs::TestFs, scenario::Scenario, trace::Tracer};
+use testio::library::{Signature, Symbols};
+use clap::{arg, Arg, ArgEnum, ArgMatches, Command};
+
+mod fuzz;
+mod run;
+
+// the options naming the read / write functions of the library, as `tester` takes them
+fn symbol_args() -> [Arg<'static>; 4] {
+    let signature = |arg: Arg<'static>| {
+        arg.required(false)
+            .default_value("ssize")
+            .possible_values(Signature::value_variants().iter().filter_map(ArgEnum::to_possible_value))
+    };
+    [
+        arg!(--"read-symbol" <SYMBOL> "The name of the read function of the library").required(false).default_value("read_all"),
+        signature(arg!(--"read-signature" <SIGNATURE> "The signature of the read function")),
+        arg!(--"write-symbol" <SYMBOL> "The name of the write function of the library").required(false).default_value("write_all"),
+        signature(arg!(--"write-signature" <SIGNATURE> "The signature of the write function")),
+    ]
+}
+
+fn symbols(matches: &ArgMatches) -> Symbols {
+    let signature = |name| Signature::from_str(matches.value_of(name).expect("defaulted"), false).expect("validated by clap");
+    Symbols {
+        read: matches.value_of("read-symbol").expect("defaulted").to_string(),
+        read_signature: signature("read-signature"),
+        write: matches.value_of("write-symbol").expect("defaulted").to_string(),
+        write_signature: signature("write-signature"),
+    }
+}
+
+fn main() {
+    let matches = Command::new("TestFs")
+        .version("wip")
+        .author("AmirB")
+        .about("Mounts a fuse that produces edge cases for simple io functions on linux")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .arg(arg!(<path> "The path to mount the fuse on"))
+        .arg(
+            arg!(--scenario <FILE> "A TOML / JSON file describing the mounted files (the built-in scenario by default)")
+                .required(false)
+        )
+        .arg(
+            arg!(--trace <FILE> "Records every file operation into FILE, one JSON object per line")
+                .required(false)
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Mounts the built-in scenario on a temporary directory and runs the read / write test matrix against a library")
+                .arg(arg!(--lib <LIB> "The path to the tested library"))
+                .args(symbol_args())
+        )
+        .subcommand(
+            Command::new("fuzz")
+                .about("Runs a library against schedules of short calls and errnos decoded from inputs (or generated from seeds)")
+                .arg(arg!(--lib <LIB> "The path to the tested library"))
+                .arg(arg!(--seed <SEED> "The seed of the first generated input").required(false).default_value("0"))
+                .arg(
+                    arg!(--iterations <N> "The amount of inputs generated when no inputs are given")
+                        .required(false)
+                        .default_value("1000")
+                )
+                .arg(arg!([inputs] ... "Input files to run, such as the corpus or the crashes of a fuzzer"))
+                .args(symbol_args())
+        )
+        .get_matches();
+
+    if let Some(("run", run_matches)) = matches.subcommand() {
+        env_logger::init();
+        let passed = run::run(Path::new(run_matches.value_of("lib").expect("required")), symbols(run_matches));
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+    if let Some(("fuzz", fuzz_matches)) = matches.subcommand() {
+        env_logger::init();
+        let inputs: Vec<PathBuf> = fuzz_matches.values_of("inputs").into_iter().flatten().map(PathBuf::from).collect();
+        let seed = fuzz_matches.value_of_t("seed").expect("Invalid seed");
+        let iterations = fuzz_matches.value_of_t("iterations").expect("Invalid iterations");
+        let library_path = Path::new(fuzz_matches.value_of("lib").expect("required"));
+        let passed = fuzz::fuzz(library_path, symbols(fuzz_matches), &inputs, seed, iterations);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
+    let path: String = matches.value_of("path").expect("required").into();
+
+    let scenario = match matches.value_of("scenario") {
+        Some(scenario_path) => Scenario::load(Path::new(scenario_path)).expect("Failed to load the scenario"),
+        None => Scenario::builtin(),
+    };
+
+    let mut fs = TestFs::new();
+    let files = scenario.build_files().expect("Failed to create the scenario files");
+    for file in files {
+        fs.add_file(file).expect("Failed to add a scenario file (invalid or duplicate path)");
+    }
+    fs.set_create_template(scenario.create_template.clone());
+    if let Some(trace_path) = matches.value_of("trace") {
+        fs.set_tracer(Tracer::create(Path::new(trace_path)).expect("Failed to create the trace file"));
+    }
+    env_logger::init();
+    fuser::mount2(fs, path, &harness::mount_options()).unwrap();
+}
@@ -0,0 +1,128 @@
+/*
+ * `testio run` - mounts the built-in scenario in-process and runs the read / write matrix of `test_read` / `test_write`
+ * (see test.py) against a library, without python.
+ */
+
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::prelude::AsRawFd;
+use std::path::Path;
+
+use libc::ssize_t;
+use testio::library::{Binding, Library, Symbols};
+use testio::{harness, rng::Rng};
+
+const READ_FILES: &[&str] = &[
+    "readempty", "readregular", "readone", "readfirstone", "readthirdshort", "readdoubling", "readX2", "readX3", "readX4",
+];
+
+const WRITE_FILES: &[&str] = &["writeone", "writeX2", "writeX3", "writeX4"];
+
+const WRITE_SIZE: usize = 10_000;
+
+type CountModifier = fn(usize) -> Option<usize>;
+
+// the counts read from a file of a given size - the whole file, or a count relative to its size
+const COUNT_MODIFIERS: &[(&str, CountModifier)] = &[
+    ("whole", |_| None),
+    ("const_5000", |size| Some(std::cmp::min(size, 5000))),
+    ("plus_1000", |size| Some(size + 1000)),
+    ("half", |size| Some(size / 2)),
+];
+
+fn read_case(read_all: &Binding<*mut u8>, path: &Path, count: Option<usize>) -> Result<(), String> {
+    let mut expected = Vec::new();
+    let reader = File::open(path).map_err(|err| format!("Failed to open the file: {}", err))?;
+    reader
+        .take(count.map_or(u64::MAX, |count| count as u64))
+        .read_to_end(&mut expected)
+        .map_err(|err| format!("Failed to read the file: {}", err))?;
+
+    let file = File::open(path).map_err(|err| format!("Failed to open the file: {}", err))?;
+    let count = match count {
+        Some(count) => count,
+        None => file.metadata().map_err(|err| format!("Failed to stat the file: {}", err))?.len() as usize,
+    };
+    let mut buffer = vec![0u8; count];
+    let result = read_all.call(file.as_raw_fd(), buffer.as_mut_ptr(), count);
+
+    if result != expected.len() as ssize_t {
+        return Err(format!("returned {}, expected {}", result, expected.len()));
+    }
+    if buffer[..expected.len()] != expected[..] {
+        return Err("the data read doesn't match the file".to_string());
+    }
+    Ok(())
+}
+
+fn write_case(write_all: &Binding<*const u8>, path: &Path, data: &[u8]) -> Result<(), String> {
+    let file = File::options().write(true).open(path).map_err(|err| format!("Failed to open the file: {}", err))?;
+    let result = write_all.call(file.as_raw_fd(), data.as_ptr(), data.len());
+    drop(file);
+
+    let written = std::fs::read(path).map_err(|err| format!("Failed to read the file back: {}", err))?;
+    // truncate the file
+    File::create(path).map_err(|err| format!("Failed to truncate the file: {}", err))?;
+
+    if result != data.len() as ssize_t {
+        return Err(format!("returned {}, expected {}", result, data.len()));
+    }
+    if written != data {
+        return Err("the file doesn't match the data written".to_string());
+    }
+    Ok(())
+}
+
+fn random_letters(seed: u64, size: usize) -> Vec<u8> {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut rng = Rng::new(seed);
+    (0..size).map(|_| LETTERS[rng.range(0, LETTERS.len() as u64 - 1) as usize]).collect()
+}
+
+// prints the outcome of a case, returning whether it passed
+fn report(name: &str, outcome: Result<(), String>) -> bool {
+    match outcome {
+        Ok(()) => {
+            println!("{} ... ok", name);
+            true
+        },
+        Err(message) => {
+            println!("{} ... FAILED: {}", name, message);
+            false
+        },
+    }
+}
+
+fn run_matrix(library: &Library, mount_path: &Path) -> (usize, usize) {
+    let read_all = library.read_all().unwrap_or_else(|err| panic!("{}", err));
+    let write_all = library.write_all().unwrap_or_else(|err| panic!("{}", err));
+    let mut outcomes = Vec::new();
+    for file_name in READ_FILES {
+        let path = mount_path.join(file_name);
+        for (modifier_name, modifier) in COUNT_MODIFIERS {
+            let outcome = std::fs::metadata(&path)
+                .map_err(|err| format!("Failed to stat the file: {}", err))
+                .and_then(|metadata| read_case(&read_all, &path, modifier(metadata.len() as usize)));
+            outcomes.push(report(&format!("test_read[{}-{}]", file_name, modifier_name), outcome));
+        }
+    }
+    for (index, file_name) in WRITE_FILES.iter().enumerate() {
+        let data = random_letters(index as u64, WRITE_SIZE);
+        let outcome = write_case(&write_all, &mount_path.join(file_name), &data);
+        outcomes.push(report(&format!("test_write[{}]", file_name), outcome));
+    }
+    let passed = outcomes.iter().filter(|passed| **passed).count();
+    (passed, outcomes.len() - passed)
+}
+
+/// Runs the matrix against the functions `symbols` names in the library at `library_path`, returning whether every case passed
+pub fn run(library_path: &Path, symbols: Symbols) -> bool {
+    let library = Library::load(library_path.as_os_str(), symbols).unwrap_or_else(|err| panic!("{}", err));
+
+    let mount = harness::mount_default().expect("Failed to mount the fuse");
+    let (passed, failed) = run_matrix(&library, mount.path());
+    drop(mount);
+
+    println!("{} passed, {} failed", passed, failed);
+    failed == 0
+}
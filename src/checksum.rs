@@ -0,0 +1,9 @@
+/// CRC-32 (IEEE 802.3), as computed by zlib
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => crc >> 1 ^ 0xedb88320,
+            _ => crc >> 1,
+        })
+    })
+}
@@ -0,0 +1,124 @@
+/*
+ * The control channel of a live mount.
+ *
+ * Every open handle of the `.control` file in the root directory is a session: commands are written
+ * to it one per line, and the response to each command (a single line) is read back from the same handle.
+ * Reads drain the pending responses and ignore the file offset.
+ *
+ * Commands:
+ *   list                - lists the paths of the mounted files
+ *   add <json spec>     - adds a file, described like a `[[file]]` entry of a scenario
+ *   replace <json spec> - replaces the file with the same name (swapping its content and policies)
+ *   remove <name>       - removes a file
+ *   reset <name>        - restores the initial content of a file and clears its stats
+ *   stats <name>        - returns the statistics of a file
+ *   unblock <name>      - serves the calls held back by a blocking file, returning how many were served
+ *   template <json>     - sets the write policy and permissions of files created through the mount,
+ *                         described like the `[create_template]` table of a scenario
+ *
+ * Files are named by their path relative to the root directory (`dir/name`), missing directories are created by `add`.
+ * Responses are `ok`, `ok <json>` or `error <message>`.
+ */
+
+use std::ffi::OsStr;
+
+use crate::scenario::{CreateTemplate, FileSpec};
+use crate::testfs::TestFs;
+
+type CommandResult = std::result::Result<Option<String>, String>;
+
+pub(crate) struct ControlSession {
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl ControlSession {
+    pub(crate) fn new() -> Self {
+        Self { input: Vec::new(), output: Vec::new() }
+    }
+
+    /// Feeds written data to the session, executing every complete command
+    pub(crate) fn write(&mut self, fs: &mut TestFs, data: &[u8]) {
+        self.input.extend_from_slice(data);
+        while let Some(end) = self.input.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.input.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let response = match execute(fs, line) {
+                Ok(None) => "ok".to_string(),
+                Ok(Some(value)) => format!("ok {}", value),
+                Err(err) => format!("error {}", err),
+            };
+            self.output.extend_from_slice(response.as_bytes());
+            self.output.push(b'\n');
+        }
+    }
+
+    /// Drains up to `size` bytes of pending responses
+    pub(crate) fn read(&mut self, size: u32) -> Vec<u8> {
+        let size = std::cmp::min(size as usize, self.output.len());
+        self.output.drain(..size).collect()
+    }
+}
+
+fn find(fs: &TestFs, name: &str) -> std::result::Result<u64, String> {
+    fs.find_file(OsStr::new(name)).ok_or_else(|| format!("no such file {:?}", name))
+}
+
+fn errno_message(err: libc::c_int) -> String {
+    std::io::Error::from_raw_os_error(err).to_string()
+}
+
+fn add(fs: &mut TestFs, spec: &str, replace: bool) -> CommandResult {
+    let spec: FileSpec = serde_json::from_str(spec).map_err(|err| err.to_string())?;
+    let file = spec.build().map_err(|err| err.to_string())?;
+    match (fs.find_file(OsStr::new(&spec.name)), replace) {
+        (Some(_), false) => Err(format!("{:?} already exists", spec.name)),
+        (None, true) => Err(format!("no such file {:?}", spec.name)),
+        (Some(ino), true) => fs.replace_file(ino, file).map(|_| None).map_err(errno_message),
+        (None, false) => fs.add_file(file).map(|_| None).map_err(errno_message),
+    }
+}
+
+fn execute(fs: &mut TestFs, line: &str) -> CommandResult {
+    let (command, args) = match line.split_once(' ') {
+        Some((command, args)) => (command, args.trim()),
+        None => (line, ""),
+    };
+    match command {
+        "list" => {
+            let names: Vec<_> = fs.file_paths().iter().map(|name| name.to_string_lossy().into_owned()).collect();
+            serde_json::to_string(&names).map(Some).map_err(|err| err.to_string())
+        },
+        "add" => add(fs, args, false),
+        "replace" => add(fs, args, true),
+        "remove" => {
+            let ino = find(fs, args)?;
+            fs.remove_file(ino).map(|_| None).map_err(errno_message)
+        },
+        "reset" => {
+            let ino = find(fs, args)?;
+            fs.reset_file(ino).map(|_| None).map_err(errno_message)
+        },
+        "stats" => {
+            let ino = find(fs, args)?;
+            let stats = fs.stats(ino).ok_or_else(|| format!("no such file {:?}", args))?;
+            serde_json::to_string(stats).map(Some).map_err(|err| err.to_string())
+        },
+        "unblock" => {
+            let ino = find(fs, args)?;
+            fs.unblock(ino).map(|count| Some(count.to_string())).map_err(errno_message)
+        },
+        "template" => {
+            let template: CreateTemplate = serde_json::from_str(args).map_err(|err| err.to_string())?;
+            // fail early on an invalid policy rather than on the next create
+            template.build(String::new(), 0o644).map_err(|err| err.to_string())?;
+            fs.set_create_template(template);
+            Ok(None)
+        },
+        _ => Err(format!("unknown command {:?}", command)),
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt;
+
+use libc::c_int;
+
+use crate::rng;
+use crate::testfs::{Delay, FileHandle};
+
+/// Describes a single `read` call, as seen by a `ReadBehavior`
+pub struct ReadCall {
+    /// The index of this call among the calls made through the same file handle
+    pub call: usize,
+    pub offset: i64,
+    /// The requested size
+    pub size: u32,
+    /// The amount of bytes between `offset` and the end of the file
+    pub remaining: usize,
+}
+
+/// Describes a single `write` call, as seen by a `WriteBehavior`
+pub struct WriteCall {
+    /// The index of this call among the calls made through the same file handle
+    pub call: usize,
+    pub offset: i64,
+    /// The amount of bytes the caller asked to write
+    pub size: u32,
+}
+
+/// The kind of a call, as seen by an `ErrnoBehavior`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoOp {
+    Read,
+    Write,
+}
+
+/// Decides how many bytes a `read` call transfers - anything above the requested size is ignored
+pub trait ReadBehavior: Send {
+    fn read_size(&mut self, call: &ReadCall) -> u32;
+}
+
+/// Decides how many bytes (from the start of the data) a `write` call writes - anything above the requested size is ignored
+pub trait WriteBehavior: Send {
+    fn write_size(&mut self, call: &WriteCall) -> u32;
+}
+
+/// Decides which calls fail, and with which errno
+pub trait ErrnoBehavior: Send {
+    fn errno(&mut self, op: IoOp, handle: &FileHandle) -> Option<c_int>;
+}
+
+/// Decides how long the replies to the `read` / `write` calls are held back
+pub trait LatencyBehavior: Send {
+    fn delay(&mut self, handle: &FileHandle) -> Delay;
+}
+
+impl<F: FnMut(&ReadCall) -> u32 + Send> ReadBehavior for F {
+    fn read_size(&mut self, call: &ReadCall) -> u32 {
+        self(call)
+    }
+}
+
+impl<F: FnMut(&WriteCall) -> u32 + Send> WriteBehavior for F {
+    fn write_size(&mut self, call: &WriteCall) -> u32 {
+        self(call)
+    }
+}
+
+impl<F: FnMut(IoOp, &FileHandle) -> Option<c_int> + Send> ErrnoBehavior for F {
+    fn errno(&mut self, op: IoOp, handle: &FileHandle) -> Option<c_int> {
+        self(op, handle)
+    }
+}
+
+impl<F: FnMut(&FileHandle) -> Delay + Send> LatencyBehavior for F {
+    fn delay(&mut self, handle: &FileHandle) -> Delay {
+        self(handle)
+    }
+}
+
+// builds a random behavior with every random part seeded with the given seed
+type Reseed = Box<dyn Fn(u64) -> FileBehavior + Send>;
+
+/*
+ * The behaviors of a `ShapedFile` - every part is optional, and calls are served in full without it.
+ * Errno behaviors are consulted in the order they were added, and the first errno returned fails the call.
+ */
+#[derive(Default)]
+pub struct FileBehavior {
+    read: Option<Box<dyn ReadBehavior>>,
+    write: Option<Box<dyn WriteBehavior>>,
+    errors: Vec<Box<dyn ErrnoBehavior>>,
+    again: Option<Box<dyn ErrnoBehavior>>,
+    latency: Option<Box<dyn LatencyBehavior>>,
+    // set on behaviors with random parts
+    seed: Option<(u64, Reseed)>,
+}
+
+impl FileBehavior {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reads(mut self, behavior: impl ReadBehavior + 'static) -> Self {
+        self.read = Some(Box::new(behavior));
+        self
+    }
+
+    pub fn writes(mut self, behavior: impl WriteBehavior + 'static) -> Self {
+        self.write = Some(Box::new(behavior));
+        self
+    }
+
+    pub fn errors(mut self, behavior: impl ErrnoBehavior + 'static) -> Self {
+        self.errors.push(Box::new(behavior));
+        self
+    }
+
+    /// Fails calls through non-blocking handles with EAGAIN before they reach the file, see `FileHandle::again_calls`
+    pub fn again(mut self, behavior: impl ErrnoBehavior + 'static) -> Self {
+        self.again = Some(Box::new(behavior));
+        self
+    }
+
+    /// Follows an errno script, which both shapes and fails the calls of its kind
+    pub fn script(self, script: ErrorScript) -> Self {
+        let behavior = match script.op {
+            IoOp::Read => self.reads(script.clone()),
+            IoOp::Write => self.writes(script.clone()),
+        };
+        behavior.errors(script)
+    }
+
+    pub fn latency(mut self, behavior: impl LatencyBehavior + 'static) -> Self {
+        self.latency = Some(Box::new(behavior));
+        self
+    }
+
+    pub fn read_size(&mut self, call: &ReadCall) -> u32 {
+        match &mut self.read {
+            Some(read) => std::cmp::min(read.read_size(call), call.size),
+            None => call.size,
+        }
+    }
+
+    pub fn write_size(&mut self, call: &WriteCall) -> u32 {
+        match &mut self.write {
+            Some(write) => std::cmp::min(write.write_size(call), call.size),
+            None => call.size,
+        }
+    }
+
+    pub fn errno(&mut self, op: IoOp, handle: &FileHandle) -> Option<c_int> {
+        self.errors.iter_mut().find_map(|errors| errors.errno(op, handle))
+    }
+
+    pub fn would_block(&mut self, op: IoOp, handle: &FileHandle) -> bool {
+        match &mut self.again {
+            Some(again) => again.errno(op, handle).is_some(),
+            None => false,
+        }
+    }
+
+    pub fn delay(&mut self, handle: &FileHandle) -> Delay {
+        match &mut self.latency {
+            Some(latency) => latency.delay(handle),
+            None => Delay::Now,
+        }
+    }
+
+    /*
+     * Marks a behavior with random parts, drawn with `seed` - `reseed` builds the behavior the parts draw with
+     * another seed, so the whole file can be replayed from a single seed.
+     */
+    pub fn seeded(mut self, seed: u64, reseed: impl Fn(u64) -> FileBehavior + Send + 'static) -> Self {
+        self.seed = Some((seed, Box::new(reseed)));
+        self
+    }
+
+    /// The seed of a random behavior, or None if every part is deterministic
+    pub fn seed(&self) -> Option<u64> {
+        self.seed.as_ref().map(|(seed, _)| *seed)
+    }
+
+    /// Replaces a random behavior with the behavior seeded with `seed`, returning false if every part is deterministic
+    pub fn reseed(&mut self, seed: u64) -> bool {
+        let reseed = match self.seed.take() {
+            Some((_, reseed)) => reseed,
+            None => return false,
+        };
+        *self = reseed(seed);
+        self.seed = Some((seed, reseed));
+        true
+    }
+}
+
+/// A single step of an errno script - consumed by one `read` / `write` call
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorStep {
+    /// Serve the call, transferring at most this many bytes
+    Ok(u32),
+    /// Fail the call with this errno
+    Err(libc::c_int),
+}
+
+// the errnos scripts and random errors can inject, by name
+const ERRNO_NAMES: &[(&str, c_int)] = &[
+    ("EINTR", libc::EINTR),
+    ("EAGAIN", libc::EAGAIN),
+    ("EIO", libc::EIO),
+    ("ENOSPC", libc::ENOSPC),
+    ("EDQUOT", libc::EDQUOT),
+    ("EFBIG", libc::EFBIG),
+    ("EPIPE", libc::EPIPE),
+    ("EBADF", libc::EBADF),
+    ("EINVAL", libc::EINVAL),
+    ("ENOMEM", libc::ENOMEM),
+    ("EFAULT", libc::EFAULT),
+];
+
+/// Returns the errno named `name` (e.g. "EINTR"), among the ones scripts and random errors can inject
+pub fn errno_from_name(name: &str) -> Option<libc::c_int> {
+    ERRNO_NAMES.iter().find(|(errno_name, _)| *errno_name == name).map(|(_, errno)| *errno)
+}
+
+fn errno_name(errno: c_int) -> Option<&'static str> {
+    ERRNO_NAMES.iter().find(|(_, value)| *value == errno).map(|(name, _)| *name)
+}
+
+impl ErrorStep {
+    /*
+     * Parses a comma separated script such as "ok 100, EINTR, ok 50, EIO".
+     * `ok <n>` serves at most n bytes, a bare `ok` serves the whole request
+     * and an errno name fails the call with that errno.
+     */
+    pub fn parse_script(script: &str) -> std::result::Result<Vec<ErrorStep>, String> {
+        script
+            .split(',')
+            .map(str::trim)
+            .filter(|step| !step.is_empty())
+            .map(|step| {
+                let mut words = step.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some("ok"), None, None) => Ok(ErrorStep::Ok(u32::MAX)),
+                    (Some("ok"), Some(size), None) => size
+                        .parse()
+                        .map(ErrorStep::Ok)
+                        .map_err(|_| format!("invalid size in step {:?}", step)),
+                    (Some(name), None, None) => errno_from_name(name)
+                        .map(ErrorStep::Err)
+                        .ok_or_else(|| format!("unknown errno {:?}", name)),
+                    _ => Err(format!("invalid step {:?}", step)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Formats the step the way `parse_script` reads it
+impl fmt::Display for ErrorStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorStep::Ok(u32::MAX) => write!(f, "ok"),
+            ErrorStep::Ok(size) => write!(f, "ok {}", size),
+            ErrorStep::Err(errno) => match errno_name(*errno) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "errno {}", errno),
+            },
+        }
+    }
+}
+
+/*
+ * An errno script followed by the `read` or `write` calls of a file - the `ok <n>` steps shape the calls
+ * (as a read / write behavior) and the errno steps fail them (as an errno behavior), `FileBehavior::script` installs both.
+ * Every file handle runs the script from its start, and once it is exhausted calls are served in full.
+ */
+#[derive(Clone, Debug)]
+pub struct ErrorScript {
+    op: IoOp,
+    steps: Vec<ErrorStep>,
+}
+
+impl ErrorScript {
+    pub fn new(op: IoOp, steps: Vec<ErrorStep>) -> Self {
+        Self { op, steps }
+    }
+
+    fn limit(&self, call: usize, size: u32) -> u32 {
+        match self.steps.get(call) {
+            Some(ErrorStep::Ok(max)) => std::cmp::min(size, *max),
+            _ => size,
+        }
+    }
+}
+
+impl ReadBehavior for ErrorScript {
+    fn read_size(&mut self, call: &ReadCall) -> u32 {
+        self.limit(call.call, call.size)
+    }
+}
+
+impl WriteBehavior for ErrorScript {
+    fn write_size(&mut self, call: &WriteCall) -> u32 {
+        self.limit(call.call, call.size)
+    }
+}
+
+impl ErrnoBehavior for ErrorScript {
+    fn errno(&mut self, op: IoOp, handle: &FileHandle) -> Option<c_int> {
+        if op != self.op {
+            return None;
+        }
+        let call = match op {
+            IoOp::Read => handle.read_calls,
+            IoOp::Write => handle.write_calls,
+        };
+        match self.steps.get(call) {
+            Some(ErrorStep::Err(err)) => Some(*err),
+            _ => None,
+        }
+    }
+}
+
+/*
+ * Fails a random share of the `read` or `write` calls of a file - every call fails with a probability of `percent`%,
+ * with an errno picked from `errnos`. Both are drawn from a PRNG seeded with `seed`, independently of the sizes a
+ * random read / write behavior with the same seed draws, so every file handle sees the same failures for a seed.
+ */
+#[derive(Clone, Debug)]
+pub struct RandomErrors {
+    op: IoOp,
+    seed: u64,
+    percent: u8,
+    errnos: Vec<c_int>,
+}
+
+impl RandomErrors {
+    pub fn new(op: IoOp, seed: u64, percent: u8, errnos: Vec<c_int>) -> Self {
+        Self { op, seed, percent, errnos }
+    }
+
+    /// Returns the errno the `call`th call on a file handle fails with, if any
+    pub fn errno_at(&self, call: usize) -> Option<c_int> {
+        if self.errnos.is_empty() {
+            return None;
+        }
+        // a stream of its own, so the failures don't follow the sizes
+        let value = rng::value_at(!self.seed, call as u64);
+        match value % 100 < self.percent as u64 {
+            true => Some(self.errnos[(value / 100) as usize % self.errnos.len()]),
+            false => None,
+        }
+    }
+}
+
+impl ErrnoBehavior for RandomErrors {
+    fn errno(&mut self, op: IoOp, handle: &FileHandle) -> Option<c_int> {
+        if op != self.op {
+            return None;
+        }
+        match op {
+            IoOp::Read => self.errno_at(handle.read_calls),
+            IoOp::Write => self.errno_at(handle.write_calls),
+        }
+    }
+}
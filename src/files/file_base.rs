@@ -1,7 +1,75 @@
+use std::ops::Range;
+
 use fuser::FileAttr;
 
 use crate::testfs::generate_fileattr;
 
+/// The sparse regions of a file - sorted, non overlapping and non adjacent ranges
+#[derive(Clone, Debug, Default)]
+pub struct Holes {
+    ranges: Vec<Range<u64>>,
+}
+
+impl Holes {
+    pub fn new(ranges: impl IntoIterator<Item = Range<u64>>) -> Self {
+        let mut holes = Self::default();
+        for range in ranges {
+            holes.punch(range);
+        }
+        holes
+    }
+
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    /// Marks `range` as a hole
+    pub fn punch(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        self.fill(range.clone());
+        let idx = self.ranges.partition_point(|hole| hole.start < range.start);
+        self.ranges.insert(idx, range);
+
+        // merge with adjacent holes
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(self.ranges.len());
+        for hole in self.ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end >= hole.start => last.end = std::cmp::max(last.end, hole.end),
+                _ => merged.push(hole),
+            }
+        }
+        self.ranges = merged;
+    }
+
+    /// Marks `range` as data
+    pub fn fill(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut remaining = Vec::with_capacity(self.ranges.len() + 1);
+        for hole in self.ranges.drain(..) {
+            if hole.end <= range.start || hole.start >= range.end {
+                remaining.push(hole);
+                continue;
+            }
+            if hole.start < range.start {
+                remaining.push(hole.start..range.start);
+            }
+            if hole.end > range.end {
+                remaining.push(range.end..hole.end);
+            }
+        }
+        self.ranges = remaining;
+    }
+
+    /// Drops the holes past `size`
+    pub fn truncate(&mut self, size: u64) {
+        self.fill(size..u64::MAX);
+    }
+}
+
 pub trait ReadableFile {
     fn get_data(&self) -> &[u8];
     
@@ -35,8 +103,18 @@ pub trait ReadableFile {
 pub trait WriteableFile: ReadableFile {
     fn get_data_mut(&mut self) -> &mut Vec<u8>;
 
+    fn get_holes_mut(&mut self) -> &mut Holes;
+
     fn _write(&mut self, offset: i64, new_data: &[u8]) -> usize
     {
+        let old_size = self.get_size() as u64;
+        let start = offset as u64;
+        let holes = self.get_holes_mut();
+        if start > old_size {
+            holes.punch(old_size..start);
+        }
+        holes.fill(start..start + new_data.len() as u64);
+
         let data = self.get_data_mut();
         let offset = offset as usize;
         
@@ -85,6 +163,11 @@ pub trait WriteableFile: ReadableFile {
             return None
         }
         if let Some(size) = size {
+            let old_size = self.get_size() as u64;
+            match size > old_size {
+                true => self.get_holes_mut().punch(old_size..size),
+                false => self.get_holes_mut().truncate(size),
+            }
             self.get_data_mut().resize(size as usize, 0);
         }
         
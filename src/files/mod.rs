@@ -1,10 +1,12 @@
 mod file_base;
-mod empty;
-mod prepopulated;
-mod readx;
-mod writex;
+mod behavior;
+mod shaped;
+mod verify;
 
-pub use prepopulated::PrepopulatedFile;
-pub use empty::EmptyROFile;
-pub use readx::ReadX;
-pub use writex::WriteX;
\ No newline at end of file
+pub use behavior::{
+    errno_from_name, ErrnoBehavior, ErrorScript, ErrorStep, FileBehavior, IoOp, LatencyBehavior, RandomErrors,
+    ReadBehavior, ReadCall, WriteBehavior, WriteCall,
+};
+pub use shaped::ShapedFile;
+pub use verify::{Expected, WriteCheck, WriteVerifier};
+pub use file_base::Holes;
@@ -0,0 +1,200 @@
+use std::ffi::OsString;
+use std::ops::Range;
+
+use fuser::FileAttr;
+
+use crate::testfs::{Delay, FileHandle, FsFile, Result};
+use crate::files::behavior::{FileBehavior, IoOp, ReadCall, WriteCall};
+use crate::files::file_base::{Holes, ReadableFile, WriteableFile};
+use crate::files::verify::{Expected, WriteCheck, WriteVerifier};
+
+/*
+ * A file whose calls are shaped by a `FileBehavior` - any combination of short reads, short writes,
+ * injected errnos and latency, on a read only or a writable file.
+ * A writable file can also check its writes against the payload it expects (see `WriteVerifier`).
+ */
+pub struct ShapedFile {
+    name: OsString,
+    data: Vec<u8>,
+    initial_data: Vec<u8>,
+    holes: Holes,
+    initial_holes: Holes,
+    writable: bool,
+    perms: Option<u16>,
+    behavior: FileBehavior,
+    verifier: Option<WriteVerifier>,
+    // the check of the last `write` call, until it is taken
+    last_check: Option<WriteCheck>,
+}
+
+impl ShapedFile {
+    /// A read only file
+    pub fn new(name: String, data: impl Into<Vec<u8>>, behavior: FileBehavior) -> Self {
+        let data = data.into();
+        Self {
+            name: name.into(),
+            initial_data: data.clone(),
+            data,
+            holes: Holes::default(),
+            initial_holes: Holes::default(),
+            writable: false,
+            perms: None,
+            behavior,
+            verifier: None,
+            last_check: None,
+        }
+    }
+
+    /// A writable file - `reset` restores `data`
+    pub fn writable(name: String, data: impl Into<Vec<u8>>, behavior: FileBehavior) -> Self {
+        Self { writable: true, ..Self::new(name, data, behavior) }
+    }
+
+    /// Makes the file sparse - the content inside the holes is zeroed, and lseek(SEEK_DATA / SEEK_HOLE) reports them
+    pub fn with_holes(mut self, holes: impl IntoIterator<Item = Range<u64>>) -> Self {
+        let holes = Holes::new(holes);
+        for hole in holes.ranges() {
+            let start = std::cmp::min(hole.start as usize, self.data.len());
+            let end = std::cmp::min(hole.end as usize, self.data.len());
+            self.data[start..end].fill(0);
+        }
+        self.initial_data = self.data.clone();
+        self.initial_holes = holes.clone();
+        self.holes = holes;
+        self
+    }
+
+    /// Checks every `write` call against the payload the file expects
+    pub fn expecting(mut self, expected: Expected) -> Self {
+        self.verifier = Some(WriteVerifier::new(expected));
+        self
+    }
+
+    /// Overrides the permissions of the file (0o444 for read only files, 0o666 for writable ones by default)
+    pub fn with_perms(mut self, perms: u16) -> Self {
+        self.perms = Some(perms);
+        self
+    }
+}
+
+impl ReadableFile for ShapedFile {
+    fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn get_perms(&self) -> u16 {
+        match (self.perms, self.writable) {
+            (Some(perms), _) => perms,
+            (None, true) => 0o666,
+            (None, false) => 0o444,
+        }
+    }
+}
+
+impl WriteableFile for ShapedFile {
+    fn get_data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+
+    fn get_holes_mut(&mut self) -> &mut Holes {
+        &mut self.holes
+    }
+}
+
+impl FsFile for ShapedFile {
+
+    fn get_name(&self) -> &std::ffi::OsStr {
+        &self.name
+    }
+
+    fn read(&mut self, handle: &mut FileHandle, offset: i64, size: u32) -> Result<&[u8]> {
+        if size == 0 {
+            return Ok(self._read(offset, 0));
+        }
+        if let Some(err) = self.behavior.errno(IoOp::Read, handle) {
+            return Err(err);
+        }
+        let call = ReadCall {
+            call: handle.read_calls,
+            offset,
+            size,
+            remaining: self.get_size().saturating_sub(offset as usize),
+        };
+        let size = self.behavior.read_size(&call);
+        Ok(self._read(offset, size))
+    }
+
+    fn getattr(&self) -> FileAttr {
+        self._getattr()
+    }
+
+    fn write(&mut self, handle: &mut FileHandle, offset: i64, data: &[u8]) -> Result<u32> {
+        if !self.writable {
+            return Err(libc::ENOSYS);
+        }
+        if let Some(err) = self.behavior.errno(IoOp::Write, handle) {
+            return Err(err);
+        }
+        let call = WriteCall { call: handle.write_calls, offset, size: data.len() as u32 };
+        let size = self.behavior.write_size(&call) as usize;
+        let written = self._write(offset, &data[..size]);
+        if let Some(verifier) = &mut self.verifier {
+            self.last_check = Some(verifier.check(offset as u64, &data[..size], &self.data));
+        }
+        Ok(written.try_into().unwrap())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.data = self.initial_data.clone();
+        self.holes = self.initial_holes.clone();
+        if let Some(verifier) = &mut self.verifier {
+            verifier.reset();
+        }
+        Ok(())
+    }
+
+    fn holes(&self) -> &[Range<u64>] {
+        self.holes.ranges()
+    }
+
+    fn would_block(&mut self, op: IoOp, handle: &FileHandle) -> bool {
+        self.behavior.would_block(op, handle)
+    }
+
+    fn delay(&mut self, handle: &mut FileHandle) -> Delay {
+        self.behavior.delay(handle)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.behavior.seed()
+    }
+
+    fn take_write_check(&mut self) -> Option<WriteCheck> {
+        self.last_check.take()
+    }
+
+    fn reseed(&mut self, seed: u64) -> Result<()> {
+        match self.behavior.reseed(seed) {
+            true => Ok(()),
+            false => Err(libc::ENOTSUP),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        flags: Option<u32>,
+    ) -> Result<FileAttr>{
+        if !self.writable {
+            return Err(libc::ENOSYS);
+        }
+        let attr = self._setattr(mode, uid, gid, size, flags).ok_or(libc::ENOSYS)?;
+        if let (Some(verifier), Some(size)) = (&mut self.verifier, size) {
+            verifier.truncate(size);
+        }
+        Ok(attr)
+    }
+}
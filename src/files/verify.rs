@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use crate::checksum::crc32;
+use crate::files::file_base::Holes;
+
+/// The payload a writable file expects to be written to it
+#[derive(Clone, Debug)]
+pub enum Expected {
+    /// The payload itself - every call is compared with it, byte by byte
+    Data(Vec<u8>),
+    /// The size and CRC-32 of the payload - the content is compared once the whole payload was written
+    Crc32 { size: u64, crc32: u32 },
+}
+
+impl Expected {
+    pub fn size(&self) -> u64 {
+        match self {
+            Expected::Data(data) => data.len() as u64,
+            Expected::Crc32 { size, .. } => *size,
+        }
+    }
+}
+
+/// The problems of a single `write` call, compared with the expected payload
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct WriteCheck {
+    /// The call wrote over bytes an earlier call already wrote
+    pub rewrite: bool,
+    /// The call started past bytes no call wrote yet, leaving a gap
+    pub skip: bool,
+    /// The first offset the call wrote a byte that isn't the expected one at (or any byte past the payload)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub divergence: Option<u64>,
+    /// The call completed the payload, and its checksum doesn't match the expected one
+    pub checksum_mismatch: bool,
+}
+
+/*
+ * Checks every `write` call of a file against the payload it expects, the way a library writing the payload
+ * should - once, in order, without gaps. A library that resends a range with the wrong offset is caught
+ * as a rewrite (or a skip), and as a divergence when the bytes don't match the payload at that offset.
+ */
+#[derive(Clone, Debug)]
+pub struct WriteVerifier {
+    expected: Expected,
+    // the ranges no call wrote yet
+    unwritten: Holes,
+}
+
+// nothing is written yet
+fn all_unwritten() -> Holes {
+    Holes::new(std::iter::once(0..u64::MAX))
+}
+
+impl WriteVerifier {
+    pub fn new(expected: Expected) -> Self {
+        Self { expected, unwritten: all_unwritten() }
+    }
+
+    /// Forgets every call, as when the file is reset
+    pub fn reset(&mut self) {
+        self.unwritten = all_unwritten();
+    }
+
+    /// Forgets the calls past `size`, as when the file is truncated
+    pub fn truncate(&mut self, size: u64) {
+        self.unwritten.punch(size..u64::MAX);
+    }
+
+    /// Checks a call that wrote `data` at `offset` - `content` is the content of the file after the call
+    pub fn check(&mut self, offset: u64, data: &[u8], content: &[u8]) -> WriteCheck {
+        if data.is_empty() {
+            return WriteCheck::default();
+        }
+        let end = offset + data.len() as u64;
+        let unwritten = self.unwritten.ranges();
+        let rewrite = !unwritten.iter().any(|range| range.start <= offset && end <= range.end);
+        let skip = unwritten.first().is_some_and(|first| first.start < offset);
+        self.unwritten.fill(offset..end);
+
+        let size = self.expected.size();
+        let divergence = match &self.expected {
+            Expected::Data(expected) => data
+                .iter()
+                .zip(offset..)
+                .find(|(byte, at)| expected.get(*at as usize) != Some(*byte))
+                .map(|(_, at)| at),
+            Expected::Crc32 { .. } if end > size => Some(std::cmp::max(offset, size)),
+            Expected::Crc32 { .. } => None,
+        };
+        // compared whenever a call leaves the whole payload written
+        let complete = offset < size && self.unwritten.ranges().first().is_none_or(|first| first.start >= size);
+        let checksum_mismatch = match &self.expected {
+            Expected::Crc32 { crc32: expected, .. } if complete && content.len() as u64 >= size => {
+                crc32(&content[..size as usize]) != *expected
+            },
+            _ => false,
+        };
+        WriteCheck { rewrite, skip, divergence, checksum_mismatch }
+    }
+}
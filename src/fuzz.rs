@@ -0,0 +1,277 @@
+/*
+ * Runs a library's `read_all` / `write_all` against schedules decoded from arbitrary bytes, so a fuzzer
+ * (or `testio fuzz`) can explore the fragmentation and errno patterns the fixed scenario files miss.
+ *
+ * An input is decoded as (missing bytes read as 0):
+ *   1 byte         - the tested function, `read_all` if even and `write_all` if odd
+ *   1 byte         - the number of steps in the schedule (modulo 33)
+ *   3 bytes a step - a kind and a little endian size: kinds below 24 fail the call with one of `ERRNOS`,
+ *                    the others transfer at most 1 + size % `MAX_STEP_SIZE` bytes
+ *   1 byte         - the count passed to `read_all`, as a share of the data (n / 128 of it, so up to twice its size)
+ *   the rest       - the data read from or written to the file, up to `MAX_DATA` bytes
+ *
+ * The schedule is served as an errno script (see `ErrorStep`) by a file of a mounted `TestFs`,
+ * and calls are served in full once it is exhausted.
+ */
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::prelude::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use libc::{c_int, ssize_t};
+
+use crate::files::{ErrorStep, IoOp};
+use crate::harness::{self, Mount};
+use crate::library::{Library, Symbols};
+use crate::scenario::Scenario;
+
+/// The errnos a schedule fails calls with - EINTR is retried by the library, the others fail it
+pub const ERRNOS: &[c_int] = &[libc::EINTR, libc::EIO, libc::ENOSPC];
+
+/// The largest amount of data a case transfers - small enough for every call to reach the file as a single request
+pub const MAX_DATA: usize = 1 << 15;
+
+const MAX_STEPS: u8 = 32;
+
+/// The largest amount of bytes a step of the schedule transfers
+pub const MAX_STEP_SIZE: u16 = 4096;
+
+// the kinds below it fail the call, about 1 in 10 steps
+const ERROR_KINDS: u8 = 24;
+
+const FILE_NAME: &str = "fuzz";
+
+// reads the input, padding it with zeros
+struct Input<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.bytes.split_first() {
+            Some((byte, rest)) => {
+                self.bytes = rest;
+                *byte
+            },
+            None => 0,
+        }
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.byte(), self.byte()])
+    }
+
+    fn rest(self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// A single call to `read_all` / `write_all`, against a schedule of short calls and errnos
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzCase {
+    pub op: IoOp,
+    pub steps: Vec<ErrorStep>,
+    /// The count passed to `read_all` - unused by `write_all`, which writes the whole data
+    pub count: usize,
+    pub data: Vec<u8>,
+}
+
+impl FuzzCase {
+    /// Decodes an input - every input is a valid case
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut input = Input { bytes };
+        let op = match input.byte() % 2 {
+            0 => IoOp::Read,
+            _ => IoOp::Write,
+        };
+        let steps = (0..input.byte() % (MAX_STEPS + 1))
+            .map(|_| match (input.byte(), input.u16()) {
+                (kind, _) if kind < ERROR_KINDS => ErrorStep::Err(ERRNOS[kind as usize % ERRNOS.len()]),
+                (_, size) => ErrorStep::Ok((1 + size % MAX_STEP_SIZE) as u32),
+            })
+            .collect();
+        let share = input.byte() as usize;
+        let data = input.rest();
+        let data = data[..std::cmp::min(data.len(), MAX_DATA)].to_vec();
+        Self { op, steps, count: data.len() * share / 128, data }
+    }
+
+    /// The schedule, as an errno script
+    pub fn script(&self) -> String {
+        self.steps.iter().map(ErrorStep::to_string).collect::<Vec<_>>().join(", ")
+    }
+
+    // whether one of the first `calls` calls failed with an errno the library shouldn't retry
+    fn failed(&self, calls: usize) -> bool {
+        self.steps.iter().take(calls).any(|step| matches!(step, ErrorStep::Err(errno) if *errno != libc::EINTR))
+    }
+
+    /*
+     * Checks the outcome of `read_all`, after the file served `calls` calls - it returns -1 once a call failed,
+     * and the count otherwise. A count larger than the data may return the size of the data (as `test_read` expects)
+     * or -1 (as `test.h` documents).
+     */
+    pub fn check_read(&self, result: ssize_t, buffer: &[u8], calls: usize) -> Result<(), String> {
+        if self.failed(calls) {
+            return match result {
+                -1 => Ok(()),
+                result => Err(format!("returned {} after a call failed, expected -1", result)),
+            };
+        }
+        let expected = std::cmp::min(self.count, self.data.len());
+        if result == -1 && self.count > self.data.len() {
+            return Ok(());
+        }
+        if result != expected as ssize_t {
+            return Err(format!("returned {}, expected {}", result, expected));
+        }
+        match divergence(&buffer[..expected], &self.data[..expected]) {
+            Some(offset) => Err(format!("the data read diverges at offset {}", offset)),
+            None => Ok(()),
+        }
+    }
+
+    /*
+     * Checks the outcome of `write_all`, after the file served `calls` calls - it returns -1 once a call failed,
+     * and the size of the data otherwise. The file holds the data, or a prefix of it after a failure.
+     */
+    pub fn check_write(&self, result: ssize_t, written: &[u8], calls: usize) -> Result<(), String> {
+        if let Some(offset) = divergence(written, &self.data[..std::cmp::min(written.len(), self.data.len())]) {
+            return Err(format!("the file diverges from the data at offset {}", offset));
+        }
+        if written.len() > self.data.len() {
+            return Err(format!("the file holds {} bytes, more than the {} written", written.len(), self.data.len()));
+        }
+        match self.failed(calls) {
+            true if result == -1 => Ok(()),
+            true => Err(format!("returned {} after a call failed, expected -1", result)),
+            false if result != self.data.len() as ssize_t => {
+                Err(format!("returned {}, expected {}", result, self.data.len()))
+            },
+            false if written.len() != self.data.len() => {
+                Err(format!("the file holds {} of the {} bytes written", written.len(), self.data.len()))
+            },
+            false => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for FuzzCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            IoOp::Read => write!(f, "read_all of {} / {} bytes", self.count, self.data.len())?,
+            IoOp::Write => write!(f, "write_all of {} bytes", self.data.len())?,
+        }
+        write!(f, ", script {:?}", self.script())
+    }
+}
+
+// the first offset where `actual` and `expected` differ, if any
+fn divergence(actual: &[u8], expected: &[u8]) -> Option<usize> {
+    match actual.iter().zip(expected).position(|(actual, expected)| actual != expected) {
+        Some(offset) => Some(offset),
+        None if actual.len() != expected.len() => Some(std::cmp::min(actual.len(), expected.len())),
+        None => None,
+    }
+}
+
+fn other_error(message: impl fmt::Display) -> io::Error {
+    io::Error::other(message.to_string())
+}
+
+/*
+ * Runs cases against a library, on a `TestFs` mounted for the lifetime of the fuzzer. Every case replaces the
+ * fuzzed file through the control channel, so a fuzz target can keep a single fuzzer for all of its inputs:
+ *
+ *     let fuzzer = Fuzzer::new(Path::new("libexample.so"), Symbols::default())?;
+ *     fuzzer.run(&FuzzCase::from_bytes(input))?;
+ */
+pub struct Fuzzer {
+    library: Library,
+    mount: Mount,
+    // the data of the read cases, loaded by the fuzzed file
+    content_path: PathBuf,
+}
+
+impl Fuzzer {
+    /// Loads the library at `library_path`, whose read / write functions `symbols` names, and mounts the fuzzed file
+    pub fn new(library_path: &Path, symbols: Symbols) -> io::Result<Self> {
+        let library = Library::load(library_path.as_os_str(), symbols)?;
+        let scenario = Scenario::from_toml(&format!("[[file]]\nname = {:?}\nwritable = true\n", FILE_NAME))
+            .map_err(other_error)?;
+        let mount = harness::mount(&scenario)?;
+        let content_path = mount.path().with_extension("fuzz-data");
+        Ok(Self { library, mount, content_path })
+    }
+
+    // replaces the fuzzed file with a file serving `case`
+    fn prepare(&self, case: &FuzzCase) -> io::Result<()> {
+        let policy = serde_json::json!({ "kind": "errno", "script": case.script() });
+        let spec = match case.op {
+            IoOp::Read => {
+                std::fs::write(&self.content_path, &case.data)?;
+                serde_json::json!({ "name": FILE_NAME, "content": { "path": self.content_path }, "read": policy })
+            },
+            IoOp::Write => serde_json::json!({ "name": FILE_NAME, "write": policy }),
+        };
+        match self.mount.control(&format!("replace {}", spec))?.as_str() {
+            "ok" => Ok(()),
+            response => Err(other_error(format!("Failed to replace the fuzzed file: {}", response))),
+        }
+    }
+
+    // the amount of `read` / `write` calls the fuzzed file served
+    fn calls(&self, op: IoOp) -> io::Result<usize> {
+        let response = self.mount.control(&format!("stats {}", FILE_NAME))?;
+        let stats: serde_json::Value = match response.strip_prefix("ok ") {
+            Some(stats) => serde_json::from_str(stats)?,
+            None => return Err(other_error(format!("Failed to get the stats of the fuzzed file: {}", response))),
+        };
+        let counter = match op {
+            IoOp::Read => "read_calls",
+            IoOp::Write => "write_calls",
+        };
+        stats[counter].as_u64().map(|calls| calls as usize).ok_or_else(|| other_error("Invalid stats"))
+    }
+
+    /// Runs `case`, returning why the library failed it - the errors of the fuzzer itself are reported as failures too
+    pub fn run(&self, case: &FuzzCase) -> Result<(), String> {
+        self.run_case(case).map_err(|err| format!("{}: {}", case, err))
+    }
+
+    fn run_case(&self, case: &FuzzCase) -> Result<(), String> {
+        self.prepare(case).map_err(|err| err.to_string())?;
+        let path = self.mount.file(FILE_NAME);
+        match case.op {
+            IoOp::Read => {
+                let read_all = self.library.read_all().map_err(|err| err.to_string())?;
+                let file = File::open(&path).map_err(|err| format!("Failed to open the file: {}", err))?;
+                let mut buffer = vec![0u8; case.count];
+                let result = read_all.call(file.as_raw_fd(), buffer.as_mut_ptr(), case.count);
+                let calls = self.calls(IoOp::Read).map_err(|err| err.to_string())?;
+                case.check_read(result, &buffer, calls)
+            },
+            IoOp::Write => {
+                let write_all = self.library.write_all().map_err(|err| err.to_string())?;
+                let file = File::options()
+                    .write(true)
+                    .open(&path)
+                    .map_err(|err| format!("Failed to open the file: {}", err))?;
+                let result = write_all.call(file.as_raw_fd(), case.data.as_ptr(), case.data.len());
+                drop(file);
+                let calls = self.calls(IoOp::Write).map_err(|err| err.to_string())?;
+                let written = std::fs::read(&path).map_err(|err| format!("Failed to read the file back: {}", err))?;
+                case.check_write(result, &written, calls)
+            },
+        }
+    }
+}
+
+impl Drop for Fuzzer {
+    fn drop(&mut self) {
+        // only created by read cases
+        let _ = std::fs::remove_file(&self.content_path);
+    }
+}
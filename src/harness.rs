@@ -0,0 +1,127 @@
+/*
+ * Mounts a `TestFs` in a background session, so Rust code can be tested against its files in-process.
+ */
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use fuser::{BackgroundSession, MountOption};
+
+use crate::scenario::Scenario;
+use crate::testfs::{TestFs, CONTROL_NAME};
+
+// tells the mount points of a process apart
+static MOUNT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/*
+ * The options the `testio` binary mounts `TestFs` with. `AllowOther` (which `AutoUnmount` implies) needs root,
+ * or `user_allow_other` in /etc/fuse.conf.
+ */
+pub fn mount_options() -> Vec<MountOption> {
+    vec![
+        MountOption::FSName("testfs".into()),
+        MountOption::AllowOther,
+        MountOption::NoAtime,
+        MountOption::AutoUnmount,
+        MountOption::DefaultPermissions,
+    ]
+}
+
+// the options of an in-process mount - only its own process uses it, and `Mount` unmounts it when dropped
+fn session_options() -> Vec<MountOption> {
+    vec![
+        MountOption::FSName("testfs".into()),
+        MountOption::NoAtime,
+        MountOption::DefaultPermissions,
+    ]
+}
+
+/// A `TestFs` mounted on a temporary directory - unmounted (and the directory removed) when dropped
+pub struct Mount {
+    session: Option<BackgroundSession>,
+    path: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl Mount {
+    /// The mount point
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path of the file named `name` in the scenario
+    pub fn file(&self, name: impl AsRef<Path>) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// The paths of the files of the scenario, in scenario order
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Runs a command of the control channel (see `scenario::FileSpec` for the specs of `add` / `replace`), returning its response line
+    pub fn control(&self, command: &str) -> io::Result<String> {
+        let mut control = File::options().read(true).write(true).open(self.path.join(CONTROL_NAME))?;
+        control.write_all(format!("{}\n", command).as_bytes())?;
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 4096];
+        while !response.ends_with(b"\n") {
+            let size = control.read(&mut buffer)?;
+            if size == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the control channel didn't respond"));
+            }
+            response.extend_from_slice(&buffer[..size]);
+        }
+        Ok(String::from_utf8_lossy(&response).trim_end().to_string())
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        // unmounts the fuse
+        self.session.take();
+        if let Err(err) = std::fs::remove_dir(&self.path) {
+            log::warn!("Failed to remove the mount point {:?}: {}", self.path, err);
+        }
+    }
+}
+
+/// Mounts the files of `scenario` on a new temporary directory
+pub fn mount(scenario: &Scenario) -> io::Result<Mount> {
+    let mut fs = TestFs::new();
+    let files = scenario.build_files().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    for file in files {
+        fs.add_file(file).map_err(io::Error::from_raw_os_error)?;
+    }
+    fs.set_create_template(scenario.create_template.clone());
+
+    let mount_index = MOUNT_COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("testio-{}-{}", std::process::id(), mount_index));
+    let files = fs.file_paths().iter().map(|name| path.join(name)).collect();
+    std::fs::create_dir(&path)?;
+    // the session is spawned directly, `fuser::spawn_mount` only takes the options as strings
+    match fuser::Session::new(fs, &path, &session_options()).and_then(fuser::Session::spawn) {
+        Ok(session) => Ok(Mount { session: Some(session), path, files }),
+        Err(err) => {
+            let _ = std::fs::remove_dir(&path);
+            Err(err)
+        },
+    }
+}
+
+/// Mounts the built-in scenario (`scenarios/default.toml`) on a new temporary directory
+///
+/// ```no_run
+/// use std::io::Read;
+///
+/// let mount = testio::harness::mount_default().expect("Failed to mount");
+/// let mut data = Vec::new();
+/// std::fs::File::open(mount.file("readX3")).unwrap().read_to_end(&mut data).unwrap();
+/// assert_eq!(data.len(), 100000);
+/// // unmounted when `mount` is dropped
+/// ```
+pub fn mount_default() -> io::Result<Mount> {
+    mount(&Scenario::builtin())
+}
@@ -1,2 +1,10 @@
 pub mod testfs;
-pub mod files;
\ No newline at end of file
+pub mod files;
+pub mod rng;
+pub mod checksum;
+pub mod scenario;
+pub mod trace;
+pub mod harness;
+pub mod library;
+pub mod fuzz;
+mod control;
\ No newline at end of file
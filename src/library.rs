@@ -0,0 +1,117 @@
+/*
+ * Binds the functions of the tested library by name, with one of a few common signatures
+ */
+
+use std::ffi::OsStr;
+use std::io;
+
+use clap::ArgEnum;
+use dlopen::raw::Library as RawLibrary;
+use libc::{c_int, size_t, ssize_t};
+
+/// The signature of a read / write function of the library
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum Signature {
+    /// `ssize_t f(int fd, void * buf, size_t size)` - returns the amount of bytes transferred, -1 on error
+    Ssize,
+    /// `int f(int fd, void * buf, size_t size, size_t * transferred)` - returns 0 on success, and the amount of bytes transferred in `transferred`
+    Status,
+    /// `bool f(int fd, void * buf, size_t size)` - returns whether all `size` bytes were transferred
+    Bool,
+}
+
+/// A read (`B` = `*mut u8`) or write (`B` = `*const u8`) function of the library
+#[derive(Clone, Copy)]
+pub enum Binding<B> {
+    Ssize(extern "C" fn(c_int, B, size_t) -> ssize_t),
+    Status(extern "C" fn(c_int, B, size_t, *mut size_t) -> c_int),
+    Bool(extern "C" fn(c_int, B, size_t) -> bool),
+}
+
+impl<B> Binding<B> {
+    /// Calls the function, converting its result to the convention of `test.h` - the amount of bytes transferred, or -1 on error
+    pub fn call(&self, fd: c_int, buf: B, size: size_t) -> ssize_t {
+        match self {
+            Binding::Ssize(function) => function(fd, buf, size),
+            Binding::Status(function) => {
+                let mut transferred: size_t = 0;
+                match function(fd, buf, size, &mut transferred) {
+                    0 => transferred as ssize_t,
+                    _ => -1,
+                }
+            },
+            Binding::Bool(function) => match function(fd, buf, size) {
+                true => size as ssize_t,
+                false => -1,
+            },
+        }
+    }
+}
+
+/// A `send_all` / `recv_all` function of `test.h`
+pub type SocketFunction<B> = extern "C" fn(c_int, B, size_t, c_int) -> ssize_t;
+
+/// The names and signatures of the read / write functions to test
+pub struct Symbols {
+    pub read: String,
+    pub read_signature: Signature,
+    pub write: String,
+    pub write_signature: Signature,
+}
+
+impl Default for Symbols {
+    /// The `read_all` / `write_all` functions of `test.h`
+    fn default() -> Self {
+        Self {
+            read: "read_all".to_string(),
+            read_signature: Signature::Ssize,
+            write: "write_all".to_string(),
+            write_signature: Signature::Ssize,
+        }
+    }
+}
+
+/// The tested library - symbols are resolved when they are first needed, so a library may export only some of them
+pub struct Library {
+    library: RawLibrary,
+    symbols: Symbols,
+}
+
+impl Library {
+    pub fn load(library_path: &OsStr, symbols: Symbols) -> io::Result<Self> {
+        let library = RawLibrary::open(library_path)
+            .map_err(|err| io::Error::other(format!("Failed to load the library: {}", err)))?;
+        Ok(Self { library, symbols })
+    }
+
+    fn symbol<T>(&self, name: &str) -> io::Result<T> {
+        unsafe { self.library.symbol::<T>(name) }
+            .map_err(|err| io::Error::other(format!("The library doesn't export {}: {}", name, err)))
+    }
+
+    fn bind<B>(&self, name: &str, signature: Signature) -> io::Result<Binding<B>> {
+        Ok(match signature {
+            Signature::Ssize => Binding::Ssize(self.symbol(name)?),
+            Signature::Status => Binding::Status(self.symbol(name)?),
+            Signature::Bool => Binding::Bool(self.symbol(name)?),
+        })
+    }
+
+    pub fn read_all(&self) -> io::Result<Binding<*mut u8>> {
+        self.bind(&self.symbols.read, self.symbols.read_signature)
+    }
+
+    pub fn write_all(&self) -> io::Result<Binding<*const u8>> {
+        self.bind(&self.symbols.write, self.symbols.write_signature)
+    }
+
+    // only needed by the socket commands
+
+    pub fn recv_all(&self) -> io::Result<SocketFunction<*mut u8>> {
+        self.symbol("recv_all")
+    }
+
+    pub fn send_all(&self) -> io::Result<SocketFunction<*const u8>> {
+        self.symbol("send_all")
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * A tiny deterministic PRNG (splitmix64).
+ * The same seed always produces the same sequence, so randomized scenarios can be replayed exactly.
+ */
+
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns the `index`th value of the sequence generated by `seed`, without generating the ones before it
+pub fn value_at(seed: u64, index: u64) -> u64 {
+    mix(seed.wrapping_add(index.wrapping_add(1).wrapping_mul(GAMMA)))
+}
+
+/// Returns the `index`th value of the sequence generated by `seed`, scaled to `min..=max`
+pub fn range_at(seed: u64, index: u64, min: u64, max: u64) -> u64 {
+    if max <= min {
+        return min;
+    }
+    match (max - min).checked_add(1) {
+        Some(span) => min + value_at(seed, index) % span,
+        // `min..=max` spans every u64
+        None => value_at(seed, index),
+    }
+}
+
+pub struct Rng {
+    seed: u64,
+    index: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, index: 0 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let value = value_at(self.seed, self.index);
+        self.index += 1;
+        value
+    }
+
+    /// Returns a value in `min..=max`
+    pub fn range(&mut self, min: u64, max: u64) -> u64 {
+        let value = range_at(self.seed, self.index, min, max);
+        self.index += 1;
+        value
+    }
+}
@@ -0,0 +1,564 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libc::c_int;
+use serde::Deserialize;
+
+use crate::files::{
+    errno_from_name, ErrnoBehavior, ErrorScript, ErrorStep, Expected, FileBehavior, IoOp, LatencyBehavior,
+    RandomErrors, ReadBehavior, ReadCall, ShapedFile, WriteBehavior, WriteCall,
+};
+use crate::rng;
+use crate::testfs::{Delay, FileHandle, FsFile};
+
+const DEFAULT_SCENARIO: &str = include_str!("../scenarios/default.toml");
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, err) => write!(f, "failed to read {:?}: {}", path, err),
+            ScenarioError::Parse(err) => write!(f, "failed to parse scenario: {}", err),
+            ScenarioError::Invalid(err) => write!(f, "invalid scenario: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+/// The initial content of a file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    /// `content = "some text"`
+    Inline(String),
+    /// `content = { repeat = "ab", count = 100 }` - the pattern repeated `count` times
+    Repeat { repeat: String, count: usize },
+    /// `content = { path = "data.bin" }` - the content of a host file, relative to the scenario file
+    Path { path: PathBuf },
+}
+
+impl Content {
+    fn load(&self) -> Result<Vec<u8>, ScenarioError> {
+        match self {
+            Content::Inline(text) => Ok(text.clone().into()),
+            Content::Repeat { repeat, count } => Ok(repeat.repeat(*count).into()),
+            Content::Path { path } => std::fs::read(path).map_err(|err| ScenarioError::Io(path.clone(), err)),
+        }
+    }
+}
+
+/// The payload a writable file expects, see `WriteVerifier`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Expect {
+    /// `expect = { size = N, crc32 = N }` - the size and CRC-32 (as computed by zlib) of the payload
+    Crc32 { size: u64, crc32: u32 },
+    /// The payload itself, given like `content`
+    Content(Content),
+}
+
+impl Expect {
+    fn load(&self) -> Result<Expected, ScenarioError> {
+        match self {
+            Expect::Crc32 { size, crc32 } => Ok(Expected::Crc32 { size: *size, crc32: *crc32 }),
+            Expect::Content(content) => content.load().map(Expected::Data),
+        }
+    }
+}
+
+fn default_min() -> u32 {
+    1
+}
+
+/// Decides how many bytes each `read` / `write` call transfers
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Policy {
+    /// Every call transfers at most `size` bytes
+    Constant { size: u32 },
+    /// Every call transfers the requested size divided by `divisor` (at least a single byte)
+    Divisor { divisor: u32 },
+    /*
+     * The nth call transfers at most `sizes[n]` bytes, where 0 serves the whole request.
+     * Once the sequence is exhausted calls are served in full, unless `repeat` is set.
+     */
+    Sequence {
+        sizes: Vec<u32>,
+        #[serde(default)]
+        repeat: bool,
+    },
+    /*
+     * Every call transfers at most a random amount of bytes in `min..=max`, drawn from a PRNG seeded with `seed`
+     * (or the `seed` of the file). `errno_percent`% of the calls fail instead, with an errno picked from `errnos`.
+     */
+    Random {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_min")]
+        min: u32,
+        max: u32,
+        #[serde(default)]
+        errnos: Vec<String>,
+        #[serde(default)]
+        errno_percent: u8,
+    },
+    /// Follows an errno script, see `ErrorStep::parse_script`
+    Errno { script: String },
+}
+
+impl Policy {
+    /// Returns the amount of bytes the `call`th call on a file handle, requesting `size` bytes, transfers
+    pub fn shape(&self, call: usize, size: u32) -> u32 {
+        let limit = match self {
+            Policy::Constant { size: limit } => *limit,
+            Policy::Divisor { divisor } => std::cmp::max(size / divisor, 1),
+            Policy::Sequence { sizes, repeat } => {
+                let idx = match repeat {
+                    true if !sizes.is_empty() => call % sizes.len(),
+                    _ => call,
+                };
+                match sizes.get(idx) {
+                    Some(0) | None => size,
+                    Some(limit) => *limit,
+                }
+            },
+            Policy::Random { seed, min, max, .. } => {
+                rng::range_at(*seed, call as u64, *min as u64, *max as u64) as u32
+            },
+            Policy::Errno { .. } => size,
+        };
+        std::cmp::min(size, limit)
+    }
+
+    /// Checks the parameters of the policy of the file `name`, e.g. that a divisor isn't 0
+    pub fn validate(&self, name: &str) -> Result<(), ScenarioError> {
+        match self {
+            // a call that transfers nothing is retried forever
+            Policy::Constant { size: 0 } => {
+                Err(ScenarioError::Invalid(format!("{}: size must be positive", name)))
+            },
+            Policy::Divisor { divisor: 0 } => {
+                Err(ScenarioError::Invalid(format!("{}: divisor must be positive", name)))
+            },
+            Policy::Random { max: 0, .. } => {
+                Err(ScenarioError::Invalid(format!("{}: max must be positive", name)))
+            },
+            Policy::Random { min, max, .. } if min > max => {
+                Err(ScenarioError::Invalid(format!("{}: min is larger than max", name)))
+            },
+            Policy::Random { errno_percent, .. } if *errno_percent >= 100 => {
+                Err(ScenarioError::Invalid(format!("{}: errno_percent must be below 100", name)))
+            },
+            Policy::Random { errnos, errno_percent, .. } if *errno_percent > 0 && errnos.is_empty() => {
+                Err(ScenarioError::Invalid(format!("{}: errno_percent requires errnos", name)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // the errnos a random policy fails calls with, if any
+    fn random_errors(&self, name: &str, op: IoOp) -> Result<Option<RandomErrors>, ScenarioError> {
+        let (seed, errnos, percent) = match self {
+            Policy::Random { seed, errnos, errno_percent, .. } if *errno_percent > 0 => (*seed, errnos, *errno_percent),
+            _ => return Ok(None),
+        };
+        let errnos = errnos
+            .iter()
+            .map(|errno| {
+                errno_from_name(errno).ok_or_else(|| ScenarioError::Invalid(format!("{}: unknown errno {:?}", name, errno)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(RandomErrors::new(op, seed, percent, errnos)))
+    }
+
+    /// The seed of a random policy, `None` for the deterministic ones
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            Policy::Random { seed, .. } => Some(*seed),
+            _ => None,
+        }
+    }
+
+    /// Makes a random policy draw its sizes and errnos with `new_seed`
+    pub fn reseed(&mut self, new_seed: u64) {
+        if let Policy::Random { seed, .. } = self {
+            *seed = new_seed;
+        }
+    }
+}
+
+impl ReadBehavior for Policy {
+    fn read_size(&mut self, call: &ReadCall) -> u32 {
+        self.shape(call.call, call.size)
+    }
+}
+
+impl WriteBehavior for Policy {
+    fn write_size(&mut self, call: &WriteCall) -> u32 {
+        self.shape(call.call, call.size)
+    }
+}
+
+/// Holds back the replies to the `read` / `write` calls of a file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Latency {
+    /*
+     * Every reply is delayed by `ms` milliseconds.
+     * An `interruptible` call fails with EINTR once the caller has a signal to handle (see `Delay::Interruptible`).
+     */
+    Fixed {
+        ms: u64,
+        #[serde(default)]
+        interruptible: bool,
+    },
+    /// Every reply is delayed by a random amount of milliseconds in `min_ms..=max_ms`, drawn from a PRNG seeded with `seed`
+    Uniform {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        min_ms: u64,
+        max_ms: u64,
+        #[serde(default)]
+        interruptible: bool,
+    },
+    /// Every call blocks until the file is unblocked through the control channel (`unblock <name>`)
+    Block,
+}
+
+impl Latency {
+    /// Returns the delay of the `call`th call on a file handle
+    pub fn delay(&self, call: usize) -> Delay {
+        let (ms, interruptible) = match self {
+            Latency::Fixed { ms, interruptible } => (*ms, *interruptible),
+            Latency::Uniform { seed, min_ms, max_ms, interruptible } => {
+                (rng::range_at(*seed, call as u64, *min_ms, *max_ms), *interruptible)
+            },
+            Latency::Block => return Delay::Blocked,
+        };
+        match interruptible {
+            true => Delay::Interruptible(Duration::from_millis(ms)),
+            false => Delay::For(Duration::from_millis(ms)),
+        }
+    }
+
+    /// The seed the delays of a uniform latency are drawn with
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            Latency::Uniform { seed, .. } => Some(*seed),
+            _ => None,
+        }
+    }
+
+    /// Draws the delays of a uniform latency with `new_seed` - fixed and blocking latencies are left as they are
+    pub fn reseed(&mut self, new_seed: u64) {
+        if let Latency::Uniform { seed, .. } = self {
+            *seed = new_seed;
+        }
+    }
+
+    fn validate(&self, name: &str) -> Result<(), ScenarioError> {
+        match self {
+            Latency::Uniform { min_ms, max_ms, .. } if min_ms > max_ms => {
+                Err(ScenarioError::Invalid(format!("{}: min_ms is larger than max_ms", name)))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl LatencyBehavior for Latency {
+    fn delay(&mut self, handle: &FileHandle) -> Delay {
+        Latency::delay(self, handle.read_calls + handle.write_calls)
+    }
+}
+
+/// Decides which `read` / `write` calls through a handle opened with O_NONBLOCK fail with EAGAIN
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Again {
+    /// `failures` calls fail before every call that is served
+    Alternate { failures: usize },
+    /// Every call fails with a probability of `percent`%, drawn from a PRNG seeded with `seed`
+    Random {
+        #[serde(default)]
+        seed: u64,
+        percent: u8,
+    },
+}
+
+impl Again {
+    /// Returns whether the `call`th call on a file handle fails with EAGAIN
+    pub fn would_block(&self, call: usize) -> bool {
+        match self {
+            Again::Alternate { failures } => match failures.checked_add(1) {
+                Some(period) => call % period < *failures,
+                None => true,
+            },
+            Again::Random { seed, percent } => rng::range_at(*seed, call as u64, 0, 99) < *percent as u64,
+        }
+    }
+
+    /// The seed a random eagain draws its failures with, `None` when the failures alternate
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            Again::Random { seed, .. } => Some(*seed),
+            _ => None,
+        }
+    }
+
+    /// Reseeds the failures of a random eagain - alternating failures have no seed
+    pub fn reseed(&mut self, new_seed: u64) {
+        if let Again::Random { seed, .. } = self {
+            *seed = new_seed;
+        }
+    }
+
+    fn validate(&self, name: &str) -> Result<(), ScenarioError> {
+        match self {
+            Again::Random { percent, .. } if *percent >= 100 => {
+                Err(ScenarioError::Invalid(format!("{}: percent must be below 100", name)))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ErrnoBehavior for Again {
+    fn errno(&mut self, _op: IoOp, handle: &FileHandle) -> Option<c_int> {
+        let nonblocking = handle.flags & libc::O_NONBLOCK != 0;
+        match nonblocking && self.would_block(handle.read_calls + handle.write_calls + handle.again_calls) {
+            true => Some(libc::EAGAIN),
+            false => None,
+        }
+    }
+}
+
+/// Describes a single mounted file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSpec {
+    pub name: String,
+    pub content: Option<Content>,
+    /// Overrides the permissions of the file
+    pub perms: Option<u16>,
+    /// Allows writing to the file, implied by a `write` policy
+    #[serde(default)]
+    pub writable: bool,
+    pub read: Option<Policy>,
+    pub write: Option<Policy>,
+    /// `holes = [[start, end], ...]` - makes a read only file sparse, the content inside the holes is zeroed
+    pub holes: Option<Vec<[u64; 2]>>,
+    /// Delays (or blocks) the replies to every `read` / `write` call
+    pub latency: Option<Latency>,
+    /// Fails some of the calls through non-blocking handles with EAGAIN
+    pub eagain: Option<Again>,
+    /// Seeds every random part of the file (policies, latency, eagain), overriding their own seeds
+    pub seed: Option<u64>,
+    /// Checks every `write` call against the payload the file expects, implies `writable`
+    pub expect: Option<Expect>,
+}
+
+fn parse_script(name: &str, script: &str) -> Result<Vec<ErrorStep>, ScenarioError> {
+    ErrorStep::parse_script(script).map_err(|err| ScenarioError::Invalid(format!("{}: {}", name, err)))
+}
+
+impl FileSpec {
+    // the seed of the first random part of the file, if any
+    fn random_seed(&self) -> Option<u64> {
+        self.read
+            .iter()
+            .chain(self.write.iter())
+            .find_map(Policy::seed)
+            .or_else(|| self.eagain.as_ref().and_then(Again::seed))
+            .or_else(|| self.latency.as_ref().and_then(Latency::seed))
+    }
+
+    // the behavior of the file, with every random part seeded with `seed` if given
+    fn behavior(&self, seed: Option<u64>) -> Result<FileBehavior, ScenarioError> {
+        let name = &self.name;
+        let (mut read, mut write) = (self.read.clone(), self.write.clone());
+        let (mut latency, mut eagain) = (self.latency.clone(), self.eagain.clone());
+        if let Some(seed) = seed {
+            read.iter_mut().chain(write.iter_mut()).for_each(|policy| policy.reseed(seed));
+            latency.iter_mut().for_each(|latency| latency.reseed(seed));
+            eagain.iter_mut().for_each(|again| again.reseed(seed));
+        }
+
+        let mut behavior = FileBehavior::new();
+        if let Some(again) = eagain {
+            behavior = behavior.again(again);
+        }
+        match read {
+            Some(Policy::Errno { script }) => {
+                behavior = behavior.script(ErrorScript::new(IoOp::Read, parse_script(name, &script)?));
+            },
+            Some(policy) => {
+                if let Some(errors) = policy.random_errors(name, IoOp::Read)? {
+                    behavior = behavior.errors(errors);
+                }
+                behavior = behavior.reads(policy);
+            },
+            None => {},
+        }
+        match write {
+            Some(Policy::Errno { script }) => {
+                behavior = behavior.script(ErrorScript::new(IoOp::Write, parse_script(name, &script)?));
+            },
+            Some(policy) => {
+                if let Some(errors) = policy.random_errors(name, IoOp::Write)? {
+                    behavior = behavior.errors(errors);
+                }
+                behavior = behavior.writes(policy);
+            },
+            None => {},
+        }
+        if let Some(latency) = latency {
+            behavior = behavior.latency(latency);
+        }
+
+        Ok(behavior)
+    }
+
+    pub fn build(&self) -> Result<Box<dyn FsFile>, ScenarioError> {
+        let name = self.name.clone();
+        let data = match &self.content {
+            Some(content) => content.load()?,
+            None => Vec::new(),
+        };
+        let writable = self.writable || self.write.is_some() || self.expect.is_some();
+        for policy in self.read.iter().chain(self.write.iter()) {
+            policy.validate(&name)?;
+        }
+        if let Some(latency) = &self.latency {
+            latency.validate(&name)?;
+        }
+        if let Some(again) = &self.eagain {
+            again.validate(&name)?;
+        }
+        if self.holes.is_some() && writable {
+            return Err(ScenarioError::Invalid(format!("{}: only read only files can have holes", name)));
+        }
+        if let Some([start, end]) = self.holes.iter().flatten().find(|[start, end]| start > end) {
+            return Err(ScenarioError::Invalid(format!("{}: invalid hole [{}, {}]", name, start, end)));
+        }
+        if let Some([start, end]) = self.holes.iter().flatten().find(|[_, end]| *end > data.len() as u64) {
+            return Err(ScenarioError::Invalid(format!("{}: hole [{}, {}] ends past the content", name, start, end)));
+        }
+
+        // every random part uses the seed the file reports, so replaying it with that seed reproduces the file
+        let behavior = match self.random_seed() {
+            Some(seed) => {
+                let seed = self.seed.unwrap_or(seed);
+                let spec = self.clone();
+                self.behavior(Some(seed))?.seeded(seed, move |seed| {
+                    spec.behavior(Some(seed)).expect("the spec was validated when the file was built")
+                })
+            },
+            None => self.behavior(None)?,
+        };
+
+        let mut file = match writable {
+            true => ShapedFile::writable(name, data, behavior),
+            false => ShapedFile::new(name, data, behavior),
+        };
+        if let Some(holes) = &self.holes {
+            file = file.with_holes(holes.iter().map(|[start, end]| *start..*end));
+        }
+        if let Some(perms) = self.perms {
+            file = file.with_perms(perms);
+        }
+        if let Some(expect) = &self.expect {
+            file = file.expecting(expect.load()?);
+        }
+        Ok(Box::new(file))
+    }
+}
+
+/// Describes the files created through the mount (e.g. `open(O_CREAT)`) - they start empty and are always writable
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTemplate {
+    pub perms: Option<u16>,
+    pub write: Option<Policy>,
+}
+
+impl CreateTemplate {
+    /// Builds a file created with `perms` (the mode of `open` without the umask), unless the template sets its own
+    pub fn build(&self, name: String, perms: u16) -> Result<Box<dyn FsFile>, ScenarioError> {
+        let spec = FileSpec {
+            name,
+            content: None,
+            perms: Some(self.perms.unwrap_or(perms)),
+            writable: true,
+            read: None,
+            write: self.write.clone(),
+            holes: None,
+            latency: None,
+            eagain: None,
+            seed: None,
+            expect: None,
+        };
+        spec.build()
+    }
+}
+
+/// A set of files to mount, usually loaded from a TOML or JSON scenario file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default, rename = "file", alias = "files")]
+    pub files: Vec<FileSpec>,
+    /// `[create_template]` - the write policy and permissions of files created through the mount
+    #[serde(default)]
+    pub create_template: CreateTemplate,
+}
+
+impl Scenario {
+    /// The scenario mounted when no scenario file is given (`scenarios/default.toml`)
+    pub fn builtin() -> Self {
+        Self::from_toml(DEFAULT_SCENARIO).expect("The built-in scenario is invalid")
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ScenarioError> {
+        toml::from_str(text).map_err(|err| ScenarioError::Parse(err.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ScenarioError> {
+        serde_json::from_str(text).map_err(|err| ScenarioError::Parse(err.to_string()))
+    }
+
+    /// Loads a scenario file - JSON if it has a `.json` extension, TOML otherwise
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let text = std::fs::read_to_string(path).map_err(|err| ScenarioError::Io(path.to_owned(), err))?;
+        let mut scenario = match path.extension().and_then(OsStr::to_str) {
+            Some("json") => Self::from_json(&text)?,
+            _ => Self::from_toml(&text)?,
+        };
+
+        // host files are relative to the scenario file
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        for file in scenario.files.iter_mut() {
+            if let Some(Content::Path { path }) = &mut file.content {
+                *path = base.join(&path);
+            }
+            if let Some(Expect::Content(Content::Path { path })) = &mut file.expect {
+                *path = base.join(&path);
+            }
+        }
+        Ok(scenario)
+    }
+
+    pub fn build_files(&self) -> Result<Vec<Box<dyn FsFile>>, ScenarioError> {
+        self.files.iter().map(FileSpec::build).collect()
+    }
+}
@@ -1,30 +1,124 @@
 use fuser::{self, Request, ReplyAttr, FileAttr, ReplyOpen, FileType};
 use fuser::Filesystem;
 use libc;
-use log::debug;
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::ops::Range;
 use std::option::Option;
-use std::time::{Duration, UNIX_EPOCH};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::control::ControlSession;
+use crate::files::{IoOp, WriteCheck};
+use crate::scenario::CreateTemplate;
+use crate::trace::{TraceRecord, Tracer};
 
 pub type Result<T> = std::result::Result<T, libc::c_int>;
 
-const INODE_BIAS: u64 = 2;
+const INODE_BIAS: u64 = 3;
 const ROOT_INODE: u64 = 1;
+const CONTROL_INODE: u64 = 2;
 const TTL: Duration = Duration::from_secs(1);
-pub trait FsFile
+
+/// The name of the control file in the root directory, see `crate::control`
+pub const CONTROL_NAME: &str = ".control";
+
+/// The state of a single open file, allocated by `TestFs::open` and passed to every call made through it
+pub struct FileHandle {
+    pub fh: u64,
+    pub ino: u64,
+    /// The flags of the open file - set at open, and updated by every `read` / `write` call (e.g. after fcntl(F_SETFL))
+    pub flags: i32,
+    /// The number of `read` calls served through this handle so far
+    pub read_calls: usize,
+    /// The number of `write` calls served through this handle so far
+    pub write_calls: usize,
+    /// The number of calls through this handle that would have blocked - they failed with EAGAIN without reaching the file
+    pub again_calls: usize,
+    /// Where the last successful call through this handle ended
+    pub next_offset: Option<i64>,
+}
+
+impl FileHandle {
+    fn new(fh: u64, ino: u64, flags: i32) -> Self {
+        Self { fh, ino, flags, read_calls: 0, write_calls: 0, again_calls: 0, next_offset: None }
+    }
+}
+
+/// How long the reply to a `read` / `write` call is held back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delay {
+    Now,
+    For(Duration),
+    /*
+     * Like `For`, but the call fails with EINTR as soon as the caller has a signal to handle,
+     * the way a slow syscall is interrupted by a signal whose handler doesn't set SA_RESTART.
+     * The delay passes before the call reaches the file, so an interrupted call is never applied.
+     */
+    Interruptible(Duration),
+    /// Until the file is unblocked, see `TestFs::unblock`
+    Blocked,
+}
+
+pub trait FsFile: Send
 {
+    // the path the file is added at, relative to the root directory - `dir/name` places it in a subdirectory
     fn get_name(&self) -> &std::ffi::OsStr;
-    fn read(&self, offset: i64, size: u32, flags: i32) -> Result<&[u8]>;
-    fn write(&mut self, offset: i64, data: &[u8], flags: i32) -> Result<u32>;
+    fn read(&mut self, handle: &mut FileHandle, offset: i64, size: u32) -> Result<&[u8]>;
+    fn write(&mut self, handle: &mut FileHandle, offset: i64, data: &[u8]) -> Result<u32>;
     fn getattr(&self) -> FileAttr;
 
-    fn open(&self, _flags:  i32) -> Result<(u64, u32)> {
-        Ok((0, fuser::consts::FOPEN_DIRECT_IO))
+    // returns the open flags (FOPEN_*) for the reply
+    fn open(&mut self, _handle: &mut FileHandle) -> Result<u32> {
+        Ok(fuser::consts::FOPEN_DIRECT_IO)
     }
 
-    fn release(&self, _flags: i32, _flush: bool) ->  Result<()> {
+    fn release(&mut self, _handle: &mut FileHandle, _flush: bool) ->  Result<()> {
         Ok(())
     }
 
+    // restores the initial content of the file - nothing to do for files that can't be written
+    fn reset(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    // the sparse regions of the file, reported by lseek(SEEK_DATA / SEEK_HOLE)
+    fn holes(&self) -> &[Range<u64>] {
+        &[]
+    }
+
+    fn lseek(&mut self, handle: &mut FileHandle, offset: i64, whence: i32) -> Result<i64> {
+        seek(self.getattr().size, self.holes(), handle, offset, whence)
+    }
+
+    // whether the next `read` / `write` call through `handle` would block - it then fails with EAGAIN, without reaching the file
+    fn would_block(&mut self, _op: IoOp, _handle: &FileHandle) -> bool {
+        false
+    }
+
+    // the delay of the reply to the next `read` / `write` call through `handle`
+    fn delay(&mut self, _handle: &mut FileHandle) -> Delay {
+        Delay::Now
+    }
+
+    // the seed the random calls of the file are drawn from - None for files without randomness
+    fn seed(&self) -> Option<u64> {
+        None
+    }
+
+    // replaces the seed of the file (the `user.testio.seed` xattr), so a random run can be replayed
+    fn reseed(&mut self, _seed: u64) -> Result<()> {
+        Err(libc::ENOTSUP)
+    }
+
+    // how the last `write` call compared with the payload the file expects - None for files that expect nothing
+    fn take_write_check(&mut self) -> Option<WriteCheck> {
+        None
+    }
+
     fn setattr(
         &mut self,
         mode: Option<u32>,
@@ -73,59 +167,690 @@ pub fn generate_fileattr(size: u64, perm: u16, is_dir: bool) -> FileAttr {
     attr
 }
 
-fn ino_to_idx(ino: u64) -> usize {
-    (ino - INODE_BIAS) as usize
+/*
+ * Calculates the result of lseek on a file of `size` bytes with the given holes.
+ * The current offset of a handle is where its last call ended.
+ */
+pub fn seek(size: u64, holes: &[Range<u64>], handle: &FileHandle, offset: i64, whence: i32) -> Result<i64> {
+    let size = size as i64;
+    let result = match whence {
+        libc::SEEK_SET => offset,
+        libc::SEEK_CUR => handle.next_offset.unwrap_or(0) + offset,
+        libc::SEEK_END => size + offset,
+        libc::SEEK_DATA | libc::SEEK_HOLE if offset < 0 || offset >= size => return Err(libc::ENXIO),
+        libc::SEEK_DATA => {
+            match holes.iter().find(|hole| hole.contains(&(offset as u64))) {
+                Some(hole) if hole.end as i64 >= size => return Err(libc::ENXIO),
+                Some(hole) => hole.end as i64,
+                None => offset,
+            }
+        },
+        libc::SEEK_HOLE => {
+            match holes.iter().find(|hole| hole.end as i64 > offset) {
+                Some(hole) => std::cmp::min(std::cmp::max(hole.start as i64, offset), size),
+                // there's an implicit hole at the end of the file
+                None => size,
+            }
+        },
+        _ => return Err(libc::EINVAL),
+    };
+    match result {
+        result if result < 0 => Err(libc::EINVAL),
+        result => Ok(result),
+    }
+}
+
+// splits a relative path (`dir/subdir/name`) into its components
+fn split_path(path: &OsStr) -> Result<Vec<&OsStr>> {
+    let components = Path::new(path)
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(libc::EINVAL),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    match components.is_empty() {
+        true => Err(libc::EINVAL),
+        false => Ok(components),
+    }
+}
+
+/// Counters of the calls made to a single file
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FileStats {
+    pub read_calls: u64,
+    pub write_calls: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The largest size requested by a single `read` call
+    pub max_read_request: u32,
+    /// The largest size requested by a single `write` call
+    pub max_write_request: u32,
+    /// The number of calls that returned an error
+    pub errors: u64,
+    /// The number of calls that didn't start where the previous call through the same handle ended
+    pub noncontiguous_calls: u64,
+    /// The number of `write` calls that wrote over bytes already written, for files expecting a payload
+    pub rewrites: u64,
+    /// The number of `write` calls that left a gap before them, for files expecting a payload
+    pub skips: u64,
+    /// The number of `write` calls that wrote bytes other than the expected ones
+    pub divergent_writes: u64,
+    /// The number of `write` calls that completed the payload with the wrong checksum
+    pub checksum_mismatches: u64,
+    /// The offset of the first byte that diverged from the expected payload, as found by the first divergent call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_divergence: Option<u64>,
+}
+
+const XATTR_RESET: &str = "user.testio.reset";
+// the seed of a random file - set it to replay a run
+const XATTR_SEED: &str = "user.testio.seed";
+
+impl FileStats {
+    // the stats exposed as extended attributes
+    fn xattrs(&self) -> Vec<(&'static str, u64)> {
+        let mut xattrs = vec![
+            ("user.testio.read_calls", self.read_calls),
+            ("user.testio.write_calls", self.write_calls),
+            ("user.testio.bytes_read", self.bytes_read),
+            ("user.testio.bytes_written", self.bytes_written),
+            ("user.testio.max_request", std::cmp::max(self.max_read_request, self.max_write_request) as u64),
+            ("user.testio.max_read_request", self.max_read_request as u64),
+            ("user.testio.max_write_request", self.max_write_request as u64),
+            ("user.testio.errors_injected", self.errors),
+            ("user.testio.noncontiguous_calls", self.noncontiguous_calls),
+            ("user.testio.rewrites", self.rewrites),
+            ("user.testio.skips", self.skips),
+            ("user.testio.divergent_writes", self.divergent_writes),
+            ("user.testio.checksum_mismatches", self.checksum_mismatches),
+        ];
+        if let Some(offset) = self.first_divergence {
+            xattrs.push(("user.testio.first_divergence", offset));
+        }
+        xattrs
+    }
+
+    fn count_request(&mut self, op: &CallOp) {
+        match op {
+            CallOp::Read { size, .. } => {
+                self.read_calls += 1;
+                self.max_read_request = std::cmp::max(self.max_read_request, *size);
+            },
+            CallOp::Write { data, .. } => {
+                self.write_calls += 1;
+                self.max_write_request = std::cmp::max(self.max_write_request, data.len() as u32);
+            },
+        }
+    }
+
+    fn count_call(&mut self, handle: &mut FileHandle, offset: i64, result: Result<usize>) {
+        if matches!(handle.next_offset, Some(next_offset) if next_offset != offset) {
+            self.noncontiguous_calls += 1;
+        }
+        match result {
+            Ok(size) => handle.next_offset = Some(offset + size as i64),
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    fn count_check(&mut self, check: &WriteCheck) {
+        self.rewrites += check.rewrite as u64;
+        self.skips += check.skip as u64;
+        self.checksum_mismatches += check.checksum_mismatch as u64;
+        if let Some(offset) = check.divergence {
+            self.divergent_writes += 1;
+            self.first_divergence.get_or_insert(offset);
+        }
+    }
+}
+
+// replies with `value`, or its size when the caller only asks for the size
+fn reply_xattr(size: u32, value: &[u8], reply: fuser::ReplyXattr) {
+    if size == 0 {
+        reply.size(value.len() as u32);
+    }
+    else if (size as usize) < value.len() {
+        reply.error(libc::ERANGE);
+    }
+    else {
+        reply.data(value);
+    }
+}
+
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// whether `pid` has a pending signal that it handles and doesn't block
+fn signal_pending(pid: u32) -> bool {
+    let status = match std::fs::read_to_string(format!("/proc/{}/status", pid)) {
+        Ok(status) => status,
+        Err(_) => return false,
+    };
+    let mask = |field: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(field))
+            .and_then(|value| u64::from_str_radix(value.trim(), 16).ok())
+            .unwrap_or(0)
+    };
+    let pending = mask("SigPnd:") | mask("ShdPnd:");
+    pending & mask("SigCgt:") & !mask("SigBlk:") != 0
+}
+
+// waits for `duration`, returning early (true) once the caller `pid` has a signal to handle
+fn interrupted(duration: Duration, pid: u32) -> bool {
+    let deadline = Instant::now() + duration;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        if signal_pending(pid) {
+            return true;
+        }
+        thread::sleep(std::cmp::min(remaining, INTERRUPT_POLL_INTERVAL));
+    }
+    false
+}
+
+// sends a reply now, or from another thread once a `Delay::For` passes
+fn delay_reply(delay: Delay, reply: impl FnOnce() + Send + 'static) {
+    match delay {
+        Delay::For(duration) => {
+            thread::spawn(move || {
+                thread::sleep(duration);
+                reply()
+            });
+        },
+        // interruptible and blocked calls are replied to once they're served, see `TestFs::dispatch`
+        _ => reply(),
+    }
+}
+
+enum CallOp {
+    Read { size: u32, reply: fuser::ReplyData },
+    Write { data: Vec<u8>, reply: fuser::ReplyWrite },
+}
+
+impl CallOp {
+    fn error(self, err: libc::c_int) {
+        match self {
+            CallOp::Read { reply, .. } => reply.error(err),
+            CallOp::Write { reply, .. } => reply.error(err),
+        }
+    }
+}
+
+// a `read` / `write` call to a file, until it is served
+struct FileCall {
+    fh: u64,
+    offset: i64,
+    // the trace record of the call, without its outcome
+    record: TraceRecord,
+    op: CallOp,
+}
+
+struct FileEntry {
+    file: Box<dyn FsFile>,
+    stats: FileStats,
+    // the calls held back until the file is unblocked - they reach the file only then
+    blocked: Vec<FileCall>,
 }
 
-fn idx_to_ino(idx: usize) -> u64 {
-    idx as u64 + INODE_BIAS
+impl FileEntry {
+    fn new(file: Box<dyn FsFile>) -> Self {
+        Self { file, stats: FileStats::default(), blocked: Vec::new() }
+    }
+}
+
+enum NodeKind {
+    File(FileEntry),
+    // the inodes of the directory entries, in the order they were added
+    Dir(Vec<u64>),
+}
+
+// a file or a directory - the root directory is its own parent
+struct Node {
+    name: OsString,
+    parent: u64,
+    kind: NodeKind,
+}
+
+impl Node {
+    fn file(&self) -> Option<&FileEntry> {
+        match &self.kind {
+            NodeKind::File(entry) => Some(entry),
+            NodeKind::Dir(_) => None,
+        }
+    }
+
+    fn file_mut(&mut self) -> Option<&mut FileEntry> {
+        match &mut self.kind {
+            NodeKind::File(entry) => Some(entry),
+            NodeKind::Dir(_) => None,
+        }
+    }
+
+    fn children(&self) -> Option<&[u64]> {
+        match &self.kind {
+            NodeKind::File(_) => None,
+            NodeKind::Dir(children) => Some(children),
+        }
+    }
+
+    fn file_type(&self) -> FileType {
+        match &self.kind {
+            NodeKind::File(_) => FileType::RegularFile,
+            NodeKind::Dir(_) => FileType::Directory,
+        }
+    }
+
+    fn getattr(&self, ino: u64) -> FileAttr {
+        let mut attr = match &self.kind {
+            NodeKind::File(entry) => entry.file.getattr(),
+            NodeKind::Dir(_) => generate_fileattr(4096, 0o755, true),
+        };
+        attr.ino = ino;
+        attr
+    }
 }
 
 pub struct TestFs {
-    files: Vec<Box<dyn FsFile>>,
+    // inodes are allocated from `next_ino` and never reused, so removing a node doesn't affect the others
+    nodes: HashMap<u64, Node>,
+    next_ino: u64,
+    // removed files that are still open - they are dropped once their last handle is released
+    orphans: HashSet<u64>,
+    create_template: CreateTemplate,
+    handles: HashMap<u64, FileHandle>,
+    control_sessions: HashMap<u64, ControlSession>,
+    next_fh: u64,
+    tracer: Option<Tracer>,
 }
 
 impl TestFs {
     pub fn new() -> Self {
-        Self { files: Vec::new() }
+        let root = Node { name: OsString::new(), parent: ROOT_INODE, kind: NodeKind::Dir(Vec::new()) };
+        Self {
+            nodes: HashMap::from([(ROOT_INODE, root)]),
+            next_ino: INODE_BIAS,
+            orphans: HashSet::new(),
+            create_template: CreateTemplate::default(),
+            handles: HashMap::new(),
+            control_sessions: HashMap::new(),
+            next_fh: 1,
+            tracer: None,
+        }
     }
 
-    pub fn add_file(&mut self, file: Box<dyn FsFile>) {
-        self.files.push(file);
+    /// Records every file operation into `tracer`
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
     }
 
-    fn get_file(&self, ino: u64) -> Option<&Box<dyn FsFile>>{
-        let idx = ino_to_idx(ino);
-        self.files.get(idx)
+    /// Sets the write policy and permissions of the files created through the mount
+    pub fn set_create_template(&mut self, template: CreateTemplate) {
+        self.create_template = template;
     }
 
-    fn get_file_mut(&mut self, ino: u64) -> Option<&mut Box<dyn FsFile>>
-    {
-        let idx = ino_to_idx(ino);
-        self.files.get_mut(idx)
+    /// Adds a file at the path returned by its `get_name`, creating the missing directories, returning its inode
+    pub fn add_file(&mut self, file: Box<dyn FsFile>) -> Result<u64> {
+        let path = file.get_name().to_owned();
+        let components = split_path(&path)?;
+        let (name, dirs) = components.split_last().ok_or(libc::EINVAL)?;
+        let parent = self.make_dirs(dirs)?;
+        self.insert_node(parent, name, NodeKind::File(FileEntry::new(file)))
+    }
+
+    /// Adds a directory and its missing parents, returning its inode
+    pub fn add_dir(&mut self, path: &OsStr) -> Result<u64> {
+        self.make_dirs(&split_path(path)?)
+    }
+
+    /// Replaces the file behind `ino`, keeping the inode and clearing its stats
+    pub fn replace_file(&mut self, ino: u64, file: Box<dyn FsFile>) -> Result<()> {
+        let entry = self.get_entry_mut(ino).ok_or(libc::ENOENT)?;
+        // calls that are blocked stay blocked, and reach the new file once it is unblocked
+        let blocked = std::mem::take(&mut entry.blocked);
+        *entry = FileEntry { blocked, ..FileEntry::new(file) };
+        Ok(())
+    }
+
+    /// Removes the file behind `ino` - the calls it holds back fail with EIO, as do the calls it would hold back
+    pub fn remove_file(&mut self, ino: u64) -> Result<()> {
+        match self.nodes.get(&ino).map(Node::file) {
+            Some(Some(_)) => {
+                self.fail_blocked(ino);
+                self.remove_node(ino)
+            },
+            Some(None) => Err(libc::EISDIR),
+            None => Err(libc::ENOENT),
+        }
+    }
+
+    /// Removes an empty directory
+    pub fn remove_dir(&mut self, ino: u64) -> Result<()> {
+        match self.nodes.get(&ino).map(Node::children) {
+            _ if ino == ROOT_INODE => Err(libc::EBUSY),
+            Some(Some([])) => self.remove_node(ino),
+            Some(Some(_)) => Err(libc::ENOTEMPTY),
+            Some(None) => Err(libc::ENOTDIR),
+            None => Err(libc::ENOENT),
+        }
+    }
+
+    /// Restores the initial content of the file behind `ino` and clears its stats
+    pub fn reset_file(&mut self, ino: u64) -> Result<()> {
+        let entry = self.get_entry_mut(ino).ok_or(libc::ENOENT)?;
+        entry.file.reset()?;
+        entry.stats = FileStats::default();
+        Ok(())
+    }
+
+    /// Serves the calls held back by the file behind `ino` (see `Delay::Blocked`), returning how many were served
+    pub fn unblock(&mut self, ino: u64) -> Result<usize> {
+        let entry = self.get_entry_mut(ino).ok_or(libc::ENOENT)?;
+        let blocked = std::mem::take(&mut entry.blocked);
+        let count = blocked.len();
+        for call in blocked {
+            self.serve(ino, call, Delay::Now);
+        }
+        Ok(count)
+    }
+
+    /// Returns the inode of the file or directory at `path` (relative to the root directory)
+    pub fn find(&self, path: &OsStr) -> Option<u64> {
+        split_path(path)
+            .ok()?
+            .into_iter()
+            .try_fold(ROOT_INODE, |parent, name| self.child(parent, name))
+    }
+
+    pub fn find_file(&self, path: &OsStr) -> Option<u64> {
+        self.find(path).filter(|ino| self.get_entry(*ino).is_some())
+    }
+
+    /// Returns the path of the file or directory behind `ino`, relative to the root directory
+    pub fn path(&self, mut ino: u64) -> Option<OsString> {
+        let mut names = Vec::new();
+        while ino != ROOT_INODE {
+            let node = self.nodes.get(&ino)?;
+            names.push(node.name.as_os_str());
+            ino = node.parent;
+        }
+        Some(names.iter().rev().collect::<PathBuf>().into_os_string())
+    }
+
+    /// Returns the paths of all the files, in the order they were added
+    pub fn file_paths(&self) -> Vec<OsString> {
+        let mut inodes: Vec<u64> = self.nodes
+            .iter()
+            .filter(|(ino, node)| node.file().is_some() && !self.orphans.contains(ino))
+            .map(|(ino, _)| *ino)
+            .collect();
+        inodes.sort_unstable();
+        inodes.into_iter().filter_map(|ino| self.path(ino)).collect()
+    }
+
+    pub fn stats(&self, ino: u64) -> Option<&FileStats> {
+        self.get_entry(ino).map(|entry| &entry.stats)
+    }
+
+    fn get_entry(&self, ino: u64) -> Option<&FileEntry> {
+        self.nodes.get(&ino)?.file()
+    }
+
+    fn get_entry_mut(&mut self, ino: u64) -> Option<&mut FileEntry> {
+        self.nodes.get_mut(&ino)?.file_mut()
+    }
+
+    fn child(&self, parent: u64, name: &OsStr) -> Option<u64> {
+        self.nodes
+            .get(&parent)?
+            .children()?
+            .iter()
+            .copied()
+            .find(|ino| matches!(self.nodes.get(ino), Some(node) if node.name == name))
+    }
+
+    // returns the inode of the directory at the path made of `names`, creating it and its parents if needed
+    fn make_dirs(&mut self, names: &[&OsStr]) -> Result<u64> {
+        let mut parent = ROOT_INODE;
+        for name in names {
+            parent = match self.child(parent, name) {
+                Some(ino) if self.get_entry(ino).is_none() => ino,
+                Some(_) => return Err(libc::ENOTDIR),
+                None => self.insert_node(parent, name, NodeKind::Dir(Vec::new()))?,
+            };
+        }
+        Ok(parent)
+    }
+
+    fn insert_node(&mut self, parent: u64, name: &OsStr, kind: NodeKind) -> Result<u64> {
+        if (parent == ROOT_INODE && name == CONTROL_NAME) || self.child(parent, name).is_some() {
+            return Err(libc::EEXIST);
+        }
+        let ino = self.next_ino;
+        match self.nodes.get_mut(&parent).map(|node| &mut node.kind) {
+            Some(NodeKind::Dir(children)) => children.push(ino),
+            Some(NodeKind::File(_)) => return Err(libc::ENOTDIR),
+            None => return Err(libc::ENOENT),
+        }
+        self.next_ino += 1;
+        self.nodes.insert(ino, Node { name: name.to_owned(), parent, kind });
+        Ok(ino)
+    }
+
+    // removes `ino` from its parent directory
+    fn detach_node(&mut self, ino: u64) -> Result<()> {
+        let parent = self.nodes.get(&ino).ok_or(libc::ENOENT)?.parent;
+        if let Some(NodeKind::Dir(children)) = self.nodes.get_mut(&parent).map(|parent| &mut parent.kind) {
+            children.retain(|child| *child != ino);
+        }
+        Ok(())
+    }
+
+    fn remove_node(&mut self, ino: u64) -> Result<()> {
+        self.detach_node(ino)?;
+        // open files keep working until they are released, like unlinked files on any other filesystem
+        self.orphans.insert(ino);
+        self.drop_orphan(ino);
+        Ok(())
+    }
+
+    fn drop_orphan(&mut self, ino: u64) {
+        if self.orphans.contains(&ino) && !self.handles.values().any(|handle| handle.ino == ino) {
+            self.orphans.remove(&ino);
+            self.nodes.remove(&ino);
+        }
+    }
+
+    // moves `ino` to `name` in the directory `new_parent`, replacing the node that is already there
+    fn move_node(&mut self, ino: u64, new_parent: u64, name: &OsStr, replace: bool) -> Result<()> {
+        let is_dir = self.get_entry(ino).is_none();
+        match self.nodes.get(&new_parent).map(Node::children) {
+            Some(Some(_)) => {},
+            Some(None) => return Err(libc::ENOTDIR),
+            None => return Err(libc::ENOENT),
+        }
+        if new_parent == ROOT_INODE && name == CONTROL_NAME {
+            return Err(libc::EEXIST);
+        }
+        // a directory can't be moved into itself
+        let mut ancestor = new_parent;
+        while ancestor != ROOT_INODE {
+            if ancestor == ino {
+                return Err(libc::EINVAL);
+            }
+            ancestor = self.nodes.get(&ancestor).ok_or(libc::ENOENT)?.parent;
+        }
+
+        match self.child(new_parent, name) {
+            Some(target) if target == ino => return Ok(()),
+            Some(_) if !replace => return Err(libc::EEXIST),
+            Some(target) => match (is_dir, self.get_entry(target).is_none()) {
+                (false, false) => self.remove_file(target)?,
+                (true, true) => self.remove_dir(target)?,
+                (false, true) => return Err(libc::EISDIR),
+                (true, false) => return Err(libc::ENOTDIR),
+            },
+            None => {},
+        }
+
+        self.detach_node(ino)?;
+        if let Some(NodeKind::Dir(children)) = self.nodes.get_mut(&new_parent).map(|parent| &mut parent.kind) {
+            children.push(ino);
+        }
+        let node = self.nodes.get_mut(&ino).ok_or(libc::ENOENT)?;
+        node.name = name.to_owned();
+        node.parent = new_parent;
+        Ok(())
+    }
+
+    // allocates a handle for the file behind `ino` and opens it, returning the handle and the open flags for the reply
+    fn open_file(&mut self, req: &Request<'_>, op: &'static str, ino: u64, flags: i32) -> Result<(u64, u32)> {
+        let mut handle = FileHandle::new(self.next_fh, ino, flags);
+        let name = self.traced_name(ino);
+        let entry = self.get_entry_mut(ino).ok_or(libc::ENOENT)?;
+        let result = entry.file.open(&mut handle);
+        let fh = handle.fh;
+        self.trace(TraceRecord {
+            fh: Some(fh),
+            ..TraceRecord::new(op, req, ino, name).with_outcome(result.map(|_| 0))
+        });
+        let open_flags = result?;
+        self.next_fh += 1;
+        self.handles.insert(fh, handle);
+        Ok((fh, open_flags))
+    }
+
+    // the path of the file behind `ino`, only when tracing (to avoid the allocation)
+    fn traced_name(&self, ino: u64) -> Option<String> {
+        self.tracer.as_ref()?;
+        self.path(ino).map(|path| path.to_string_lossy().into_owned())
+    }
+
+    fn trace(&mut self, record: TraceRecord) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(&record);
+        }
+    }
+
+    // serves a call after the delay of its file - a blocked call is held, and reaches the file once it is unblocked
+    fn dispatch(&mut self, ino: u64, pid: u32, call: FileCall, delay: Delay) {
+        match delay {
+            Delay::Blocked => {
+                if let Some(entry) = self.get_entry_mut(ino) {
+                    entry.blocked.push(call);
+                }
+                if self.orphans.contains(&ino) {
+                    self.fail_blocked(ino);
+                }
+            },
+            // waited for here, so an interrupted call never reaches the file - the other calls wait meanwhile
+            Delay::Interruptible(duration) => match interrupted(duration, pid) {
+                true => self.fail(ino, call, libc::EINTR),
+                false => self.serve(ino, call, Delay::Now),
+            },
+            delay => self.serve(ino, call, delay),
+        }
+    }
+
+    // applies the call to its file, counts and traces it, and replies with the result after `delay`
+    fn serve(&mut self, ino: u64, call: FileCall, delay: Delay) {
+        let FileCall { fh, offset, record, op } = call;
+        let entry = match self.nodes.get_mut(&ino).and_then(Node::file_mut) {
+            Some(entry) => entry,
+            None => return op.error(libc::ENOENT),
+        };
+        let handle = match self.handles.get_mut(&fh) {
+            Some(handle) if handle.ino == ino => handle,
+            _ => return op.error(libc::EBADF),
+        };
+        let record = TraceRecord { seed: entry.file.seed(), ..record };
+        let stats = &mut entry.stats;
+        stats.count_request(&op);
+        match op {
+            CallOp::Read { size, reply } => {
+                let result = entry.file.read(handle, offset, size);
+                handle.read_calls += 1;
+                let outcome = result.as_ref().map(|data| data.len() as u64).map_err(|err| *err);
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.record(&record.with_outcome(outcome));
+                }
+                stats.count_call(handle, offset, outcome.map(|size| size as usize));
+                match result {
+                    Ok(data) => {
+                        stats.bytes_read += data.len() as u64;
+                        match delay {
+                            Delay::Now => reply.data(data),
+                            delay => {
+                                let data = data.to_vec();
+                                delay_reply(delay, move || reply.data(&data))
+                            },
+                        }
+                    },
+                    Err(err) => delay_reply(delay, move || reply.error(err)),
+                }
+            },
+            CallOp::Write { data, reply } => {
+                let result = entry.file.write(handle, offset, &data);
+                let check = entry.file.take_write_check();
+                handle.write_calls += 1;
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.record(&TraceRecord { check, ..record.with_outcome(result.map(u64::from)) });
+                }
+                stats.count_call(handle, offset, result.map(|size| size as usize));
+                if let Some(check) = &check {
+                    stats.count_check(check);
+                }
+                match result {
+                    Ok(size) => {
+                        stats.bytes_written += size as u64;
+                        delay_reply(delay, move || reply.written(size))
+                    },
+                    Err(err) => delay_reply(delay, move || reply.error(err)),
+                }
+            },
+        }
+    }
+
+    // fails a call before it reaches its file - it is counted and traced, but the file never sees it
+    fn fail(&mut self, ino: u64, call: FileCall, err: libc::c_int) {
+        let FileCall { fh, offset, record, op } = call;
+        let entry = self.nodes.get_mut(&ino).and_then(Node::file_mut);
+        if let (Some(entry), Some(handle)) = (entry, self.handles.get_mut(&fh)) {
+            entry.stats.count_request(&op);
+            entry.stats.count_call(handle, offset, Err(err));
+            let record = TraceRecord { seed: entry.file.seed(), ..record.with_outcome(Err(err)) };
+            self.trace(record);
+        }
+        op.error(err);
+    }
+
+    // fails the calls held back by a removed file with EIO - it can't be unblocked through the control channel anymore
+    fn fail_blocked(&mut self, ino: u64) {
+        let blocked = match self.get_entry_mut(ino) {
+            Some(entry) => std::mem::take(&mut entry.blocked),
+            None => return,
+        };
+        for call in blocked {
+            self.fail(ino, call, libc::EIO);
+        }
+    }
+
+    fn control_attr() -> FileAttr {
+        let mut attr = generate_fileattr(0, 0o666, false);
+        attr.ino = CONTROL_INODE;
+        attr
     }
 }
 
 impl Filesystem for TestFs {
     fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr)
     {
-        // root '/' dir
-        if ino == ROOT_INODE {
-            let mut attr = generate_fileattr(4096, 0o755, true);
-            attr.ino = ino;
-            reply.attr(&TTL, &attr);
-        }
-        else {
-            let file = match self.get_file(ino) {
-                Some(file) => file,
-                None => return reply.error(libc::ENOENT),
-            };
-            let attr = {
-                let mut attr = file.getattr();
-                attr.ino = ino;
-                attr
-            };
-            reply.attr(&TTL, &attr);
+        if ino == CONTROL_INODE {
+            return reply.attr(&TTL, &Self::control_attr());
+        }
+        match self.nodes.get(&ino) {
+            Some(node) => reply.attr(&TTL, &node.getattr(ino)),
+            None => reply.error(libc::ENOENT),
         }
     }
 
@@ -140,29 +865,26 @@ impl Filesystem for TestFs {
             "lookup(parent: {:#x?}, name {:?})",
             parent, name
         );
-        if parent != ROOT_INODE {
-            reply.error(libc::EBADF);
-            return;
-        }
-        for (idx, file) in self.files.iter().enumerate() {
-            if file.get_name() == name {
-                let attr = {
-                    let mut attr = file.getattr();
-                    attr.ino = idx_to_ino(idx);
-                    attr
-                };
-                reply.entry(&TTL, &attr, 1);
-                return;
-            }
+        if parent == ROOT_INODE && name == CONTROL_NAME {
+            return reply.entry(&TTL, &Self::control_attr(), 1);
+        }
+        match self.nodes.get(&parent).map(Node::children) {
+            Some(Some(_)) => {},
+            Some(None) => return reply.error(libc::ENOTDIR),
+            None => return reply.error(libc::ENOENT),
         }
-        reply.error(libc::ENOENT);
+        let ino = match self.child(parent, name) {
+            Some(ino) => ino,
+            None => return reply.error(libc::ENOENT),
+        };
+        reply.entry(&TTL, &self.nodes[&ino].getattr(ino), 1);
     }
 
     fn forget(&mut self, _req: &Request<'_>, _ino: u64, _nlookup: u64) {}
 
     fn setattr(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         mode: Option<u32>,
         uid: Option<u32>,
@@ -178,48 +900,182 @@ impl Filesystem for TestFs {
         flags: Option<u32>,
         reply: ReplyAttr,
     ) {
-        let file = match self.get_file_mut(ino) {
-            Some(file) => file,
+        // truncating the control file (e.g. opening it with O_TRUNC) does nothing
+        if ino == CONTROL_INODE {
+            return reply.attr(&TTL, &Self::control_attr());
+        }
+        let name = self.traced_name(ino);
+        let entry = match self.get_entry_mut(ino) {
+            Some(entry) => entry,
             None => return reply.error(libc::ENOENT)
         };
-        match file.setattr(mode, uid, gid, size, flags) {
-            Ok(attr) => reply.attr(&TTL, &attr),
+        let result = entry.file.setattr(mode, uid, gid, size, flags);
+        self.trace(TraceRecord {
+            size,
+            ..TraceRecord::new("setattr", req, ino, name).with_outcome(result.map(|attr| attr.size))
+        });
+        match result {
+            Ok(mut attr) => {
+                attr.ino = ino;
+                reply.attr(&TTL, &attr)
+            },
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
+        debug!("unlink(parent: {:#x?}, name: {:?})", parent, name);
+        let ino = match self.child(parent, name) {
+            Some(ino) => ino,
+            None => return reply.error(libc::ENOENT),
+        };
+        let name = self.traced_name(ino);
+        let result = self.remove_file(ino);
+        self.trace(TraceRecord::new("unlink", req, ino, name).with_outcome(result.map(|_| 0)));
+        match result {
+            Ok(()) => reply.ok(),
             Err(err) => reply.error(err),
         }
     }
 
-    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: fuser::ReplyEmpty) {
+    fn rename(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
         debug!(
-            "[Not Implemented] unlink(parent: {:#x?}, name: {:?})",
-            parent, name,
+            "rename(parent: {:#x?}, name: {:?}, newparent: {:#x?}, newname: {:?}, flags: {:#x?})",
+            parent, name, newparent, newname, flags
         );
-        reply.error(libc::ENOSYS);
+        let ino = match self.child(parent, name) {
+            Some(ino) => ino,
+            None => return reply.error(libc::ENOENT),
+        };
+        let result = match flags {
+            0 => self.move_node(ino, newparent, newname, true),
+            libc::RENAME_NOREPLACE => self.move_node(ino, newparent, newname, false),
+            _ => Err(libc::EINVAL),
+        };
+        // traced with the new path
+        let name = self.traced_name(ino);
+        self.trace(TraceRecord::new("rename", req, ino, name).with_outcome(result.map(|_| 0)));
+        match result {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
     }
 
-    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
-        let file = match self.get_file(ino) {
-            Some(file) => file,
-            None => return reply.error(libc::ENOENT)
+    fn create(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: fuser::ReplyCreate,
+    ) {
+        debug!(
+            "create(parent: {:#x?}, name: {:?}, mode: {:o}, umask: {:o}, flags: {:#x?})",
+            parent, name, mode, umask, flags
+        );
+        let path = match self.path(parent) {
+            Some(path) => Path::new(&path).join(name),
+            None => return reply.error(libc::ENOENT),
         };
-        match file.open(flags) {
-            Ok((fh, flags)) => reply.opened(fh, flags),
-            Err(err) => reply.error(err)
+        let perms = (mode & !umask & 0o7777) as u16;
+        let file = match self.create_template.build(path.to_string_lossy().into_owned(), perms) {
+            Ok(file) => file,
+            Err(err) => {
+                warn!("Failed to create {:?} from the create template: {}", path, err);
+                return reply.error(libc::EINVAL);
+            },
         };
+        let entry = FileEntry::new(file);
+        let ino = match self.insert_node(parent, name, NodeKind::File(entry)) {
+            Ok(ino) => ino,
+            Err(err) => return reply.error(err),
+        };
+        let attr = self.nodes[&ino].getattr(ino);
+        match self.open_file(req, "create", ino, flags) {
+            Ok((fh, open_flags)) => reply.created(&TTL, &attr, 0, fh, open_flags),
+            Err(err) => reply.error(err),
+        }
     }
-    
+
+    fn mkdir(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        reply: fuser::ReplyEntry,
+    ) {
+        debug!(
+            "mkdir(parent: {:#x?}, name: {:?}, mode: {:o}, umask: {:o})",
+            parent, name, mode, umask
+        );
+        let ino = match self.insert_node(parent, name, NodeKind::Dir(Vec::new())) {
+            Ok(ino) => ino,
+            Err(err) => return reply.error(err),
+        };
+        let name = self.traced_name(ino);
+        self.trace(TraceRecord::new("mkdir", req, ino, name).with_outcome(Ok(0)));
+        reply.entry(&TTL, &self.nodes[&ino].getattr(ino), 1)
+    }
+
+    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        debug!("rmdir(parent: {:#x?}, name: {:?})", parent, name);
+        let ino = match self.child(parent, name) {
+            Some(ino) => ino,
+            None => return reply.error(libc::ENOENT),
+        };
+        let name = self.traced_name(ino);
+        let result = self.remove_dir(ino);
+        self.trace(TraceRecord::new("rmdir", req, ino, name).with_outcome(result.map(|_| 0)));
+        match result {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
+        if ino == CONTROL_INODE {
+            let fh = self.next_fh;
+            self.next_fh += 1;
+            self.control_sessions.insert(fh, ControlSession::new());
+            // the offset is ignored by the session, direct io is enough to keep the kernel from caching responses
+            return reply.opened(fh, fuser::consts::FOPEN_DIRECT_IO);
+        }
+        match self.open_file(req, "open", ino, flags) {
+            Ok((fh, open_flags)) => reply.opened(fh, open_flags),
+            Err(err) => reply.error(err),
+        }
+    }
+
     fn opendir(
         &mut self,
         _req: &Request<'_>,
-        _ino: u64,
+        ino: u64,
         _flags: i32,
         reply: ReplyOpen
     ) {
-        reply.opened(0, 0);
+        match self.nodes.get(&ino).map(Node::children) {
+            Some(Some(_)) => reply.opened(0, 0),
+            Some(None) => reply.error(libc::ENOTDIR),
+            None => reply.error(libc::ENOENT),
+        }
     }
 
     fn read(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         fh: u64,
         offset: i64,
@@ -234,19 +1090,47 @@ impl Filesystem for TestFs {
             ino, fh, offset, size, flags, lock_owner
         );
 
-        let file = match self.get_file(ino) {
-            Some(file) => file,
+        if ino == CONTROL_INODE {
+            return match self.control_sessions.get_mut(&fh) {
+                Some(session) => reply.data(&session.read(size)),
+                None => reply.error(libc::EBADF),
+            };
+        }
+        let name = self.traced_name(ino);
+        let entry = match self.nodes.get_mut(&ino).and_then(Node::file_mut) {
+            Some(entry) => entry,
             None => return reply.error(libc::ENOENT),
         };
-        match file.read(offset, size, flags) {
-            Ok(data) => reply.data(data),
-            Err(err) => reply.error(err),
+        let handle = match self.handles.get_mut(&fh) {
+            Some(handle) if handle.ino == ino => handle,
+            _ => return reply.error(libc::EBADF),
+        };
+        handle.flags = flags;
+        let would_block = entry.file.would_block(IoOp::Read, handle);
+        if would_block {
+            handle.again_calls += 1;
+        }
+        let delay = entry.file.delay(handle);
+        let call = FileCall {
+            fh,
+            offset,
+            record: TraceRecord {
+                fh: Some(fh),
+                offset: Some(offset),
+                size: Some(size as u64),
+                ..TraceRecord::new("read", req, ino, name)
+            },
+            op: CallOp::Read { size, reply },
         };
+        match would_block {
+            true => self.fail(ino, call, libc::EAGAIN),
+            false => self.dispatch(ino, req.pid(), call, delay),
+        }
     }
 
     fn write(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         fh: u64,
         offset: i64,
@@ -267,14 +1151,45 @@ impl Filesystem for TestFs {
             flags,
             lock_owner
         );
-        let file = match self.get_file_mut(ino) {
-            Some(file) => file,
+        if ino == CONTROL_INODE {
+            let mut session = match self.control_sessions.remove(&fh) {
+                Some(session) => session,
+                None => return reply.error(libc::EBADF),
+            };
+            session.write(self, data);
+            self.control_sessions.insert(fh, session);
+            return reply.written(data.len() as u32);
+        }
+        let name = self.traced_name(ino);
+        let entry = match self.nodes.get_mut(&ino).and_then(Node::file_mut) {
+            Some(entry) => entry,
             None => return reply.error(libc::ENOENT),
         };
-        match file.write(offset, data, flags) {
-            Ok(size) => reply.written(size),
-            Err(err) => reply.error(err),
+        let handle = match self.handles.get_mut(&fh) {
+            Some(handle) if handle.ino == ino => handle,
+            _ => return reply.error(libc::EBADF),
         };
+        handle.flags = flags;
+        let would_block = entry.file.would_block(IoOp::Write, handle);
+        if would_block {
+            handle.again_calls += 1;
+        }
+        let delay = entry.file.delay(handle);
+        let call = FileCall {
+            fh,
+            offset,
+            record: TraceRecord {
+                fh: Some(fh),
+                offset: Some(offset),
+                size: Some(data.len() as u64),
+                ..TraceRecord::new("write", req, ino, name)
+            },
+            op: CallOp::Write { data: data.to_vec(), reply },
+        };
+        match would_block {
+            true => self.fail(ino, call, libc::EAGAIN),
+            false => self.dispatch(ino, req.pid(), call, delay),
+        }
     }
 
     fn flush(&mut self, _req: &Request<'_>, ino: u64, fh: u64, lock_owner: u64, reply: fuser::ReplyEmpty) {
@@ -287,19 +1202,37 @@ impl Filesystem for TestFs {
 
     fn release(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
-        _fh: u64,
-        flags: i32,
+        fh: u64,
+        _flags: i32,
         _lock_owner: Option<u64>,
         flush: bool,
         reply: fuser::ReplyEmpty,
     ) {
-        let file = match self.get_file(ino) {
-            Some(file) => file,
-            None => return reply.error(libc::ENOENT)
+        if ino == CONTROL_INODE {
+            return match self.control_sessions.remove(&fh) {
+                Some(_) => reply.ok(),
+                None => reply.error(libc::EBADF),
+            };
+        }
+        let mut handle = match self.handles.remove(&fh) {
+            Some(handle) if handle.ino == ino => handle,
+            _ => return reply.error(libc::EBADF),
         };
-        match file.release(flags, flush) {
+        let name = self.traced_name(ino);
+        // the file was removed while it was open
+        let entry = match self.get_entry_mut(ino) {
+            Some(entry) => entry,
+            None => return reply.ok()
+        };
+        let result = entry.file.release(&mut handle, flush);
+        self.drop_orphan(ino);
+        self.trace(TraceRecord {
+            fh: Some(fh),
+            ..TraceRecord::new("release", req, ino, name).with_outcome(result.map(|_| 0))
+        });
+        match result {
             Ok(()) => reply.ok(),
             Err(err) => reply.error(err)
         };
@@ -325,38 +1258,92 @@ impl Filesystem for TestFs {
             "readdir(ino: {:#x?}, fh: {}, offset: {})",
             ino, fh, offset
         );
-        if ino != ROOT_INODE {
-            reply.error(libc::ENOENT);
-            return;
-        }
-        let count = self.files.len();
-        // if offset == count, we should return EOF - fallthrough to the loop which will return an empty reply
-        if offset < 0 || offset as usize > count {
-            reply.error(libc::EINVAL);
-            return;
-        }
-        let offset = offset as usize;
-        
-        let iter = self.files.iter().enumerate().skip(offset);
-        for (idx, file) in iter {
-            let ino = idx_to_ino(idx);
-            let name = file.get_name();
-            // offset is the index of the next entry
-            let offset = idx as i64 + 1;
-            let kind = FileType::RegularFile;
-            if !reply.add(
-                ino,
-                offset,
-                kind,
-                name
-            ) {
+        let node = match self.nodes.get(&ino) {
+            Some(node) => node,
+            None => return reply.error(libc::ENOENT),
+        };
+        let children = match node.children() {
+            Some(children) => children,
+            None => return reply.error(libc::ENOTDIR),
+        };
+        if offset < 0 {
+            return reply.error(libc::EINVAL);
+        }
+
+        let dots = [(ino, FileType::Directory, OsStr::new(".")), (node.parent, FileType::Directory, OsStr::new(".."))];
+        let entries = dots.into_iter().chain(children.iter().filter_map(|child| {
+            let node = self.nodes.get(child)?;
+            Some((*child, node.file_type(), node.name.as_os_str()))
+        }));
+        for (idx, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+            // offset is the index of the next entry, add returns true once the reply is full
+            if reply.add(ino, idx as i64 + 1, kind, name) {
                 break;
             }
         }
         reply.ok();
+    }
+
+    fn setxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        _flags: i32,
+        _position: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        debug!("setxattr(ino: {:#x?}, name: {:?})", ino, name);
+        let entry = match self.get_entry_mut(ino) {
+            Some(entry) => entry,
+            None => return reply.error(libc::ENOTSUP),
+        };
+        if name == XATTR_SEED {
+            let seed = std::str::from_utf8(value).ok().and_then(|value| value.trim().parse().ok());
+            return match seed.map(|seed| entry.file.reseed(seed)) {
+                Some(Ok(())) => reply.ok(),
+                Some(Err(err)) => reply.error(err),
+                None => reply.error(libc::EINVAL),
+            };
+        }
+        if name != XATTR_RESET {
+            return reply.error(libc::ENOTSUP);
+        }
+        entry.stats = FileStats::default();
+        reply.ok();
+    }
 
+    fn getxattr(&mut self, _req: &Request<'_>, ino: u64, name: &OsStr, size: u32, reply: fuser::ReplyXattr) {
+        debug!("getxattr(ino: {:#x?}, name: {:?}, size: {})", ino, name, size);
+        let entry = match self.get_entry(ino) {
+            Some(entry) => entry,
+            None => return reply.error(libc::ENODATA),
+        };
+        let value = match name == XATTR_SEED {
+            true => entry.file.seed(),
+            false => entry.stats.xattrs().iter().find(|(xattr, _)| name == *xattr).map(|(_, value)| *value),
+        };
+        match value {
+            Some(value) => reply_xattr(size, value.to_string().as_bytes(), reply),
+            None => reply.error(libc::ENODATA),
+        }
     }
 
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: fuser::ReplyXattr) {
+        debug!("listxattr(ino: {:#x?}, size: {})", ino, size);
+        let entry = match self.get_entry(ino) {
+            Some(entry) => entry,
+            None => return reply_xattr(size, &[], reply),
+        };
+        let mut names = Vec::new();
+        let seed = entry.file.seed().map(|_| XATTR_SEED);
+        for xattr in entry.stats.xattrs().iter().map(|(xattr, _)| *xattr).chain(seed) {
+            names.extend_from_slice(xattr.as_bytes());
+            names.push(0);
+        }
+        reply_xattr(size, &names, reply);
+    }
 
     fn access(&mut self, _req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
         debug!("[Not Implemented] access(ino: {:#x?}, mask: {})", ino, mask);
@@ -365,7 +1352,7 @@ impl Filesystem for TestFs {
 
     fn lseek(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         fh: u64,
         offset: i64,
@@ -373,9 +1360,35 @@ impl Filesystem for TestFs {
         reply: fuser::ReplyLseek,
     ) {
         debug!(
-            "[Not Implemented] lseek(ino: {:#x?}, fh: {}, offset: {}, whence: {})",
+            "lseek(ino: {:#x?}, fh: {}, offset: {}, whence: {})",
             ino, fh, offset, whence
         );
-        reply.error(libc::ENOSYS);
+        if ino == CONTROL_INODE {
+            return reply.error(libc::ESPIPE);
+        }
+        let name = self.traced_name(ino);
+        let entry = match self.nodes.get_mut(&ino).and_then(Node::file_mut) {
+            Some(entry) => entry,
+            None => return reply.error(libc::ENOENT),
+        };
+        let handle = match self.handles.get_mut(&fh) {
+            Some(handle) if handle.ino == ino => handle,
+            _ => return reply.error(libc::EBADF),
+        };
+        let result = entry.file.lseek(handle, offset, whence);
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(&TraceRecord {
+                fh: Some(fh),
+                offset: Some(offset),
+                ..TraceRecord::new("lseek", req, ino, name).with_outcome(result.map(|offset| offset as u64))
+            });
+        }
+        match result {
+            Ok(offset) => {
+                handle.next_offset = Some(offset);
+                reply.offset(offset)
+            },
+            Err(err) => reply.error(err),
+        }
     }
 }
\ No newline at end of file
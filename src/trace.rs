@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use fuser::Request;
+use log::warn;
+use serde::Serialize;
+
+use crate::files::WriteCheck;
+
+/// A single traced operation - serialized as one JSON line
+#[derive(Debug, Serialize)]
+pub struct TraceRecord {
+    pub op: &'static str,
+    pub ino: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fh: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// The requested size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The returned size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errno: Option<i32>,
+    /// The seed of a random file - replays the run when set back through the `user.testio.seed` xattr
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// How a `write` call compared with the payload the file expects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<WriteCheck>,
+    /// Seconds since the epoch
+    pub timestamp: f64,
+    pub pid: u32,
+}
+
+impl TraceRecord {
+    pub fn new(op: &'static str, req: &Request<'_>, ino: u64, name: Option<String>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64())
+            .unwrap_or_default();
+        Self {
+            op,
+            ino,
+            name,
+            fh: None,
+            offset: None,
+            size: None,
+            result: None,
+            errno: None,
+            seed: None,
+            check: None,
+            timestamp,
+            pid: req.pid(),
+        }
+    }
+
+    /// Records the outcome of the operation - the returned size or errno
+    pub fn with_outcome(mut self, outcome: std::result::Result<u64, libc::c_int>) -> Self {
+        match outcome {
+            Ok(size) => self.result = Some(size),
+            Err(err) => self.errno = Some(err),
+        }
+        self
+    }
+}
+
+/// Writes `TraceRecord`s to a JSON lines file, flushing every record so the trace can be read during the run
+pub struct Tracer {
+    out: LineWriter<File>,
+}
+
+impl Tracer {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self { out: LineWriter::new(File::create(path)?) })
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) {
+        let result = serde_json::to_writer(&mut self.out, record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| self.out.write_all(b"\n"));
+        if let Err(err) = result {
+            warn!("Failed to write trace record {:?}: {}", record, err);
+        }
+    }
+}
@@ -24,3 +24,33 @@ ssize_t read_all(int fd, void * buf, size_t buf_size);
  *           Failing to write exactly buf_size bytes is considered an error
  */
 ssize_t write_all(int fd, const void * buf, size_t buf_size);
+
+/*
+ * The socket functions are optional - they are only needed by the socket tests.
+ */
+
+/**
+ * This function receives `buf_size` bytes from socket `sockfd` to the buffer `buf`
+ * 
+ * @param sockfd - the socket to receive from
+ * @param buf - the buffer to receive into - must be large enought to hold `buf_size` bytes
+ * @param buf_size - the amount of bytes to receive
+ * @param flags - passed to `recv`
+ * 
+ * @returns buf_size on success, -1 on error.
+ *           Failing to receive exactly buf_size bytes (for example - if the peer closes the connection) is considered an error
+ */
+ssize_t recv_all(int sockfd, void * buf, size_t buf_size, int flags);
+
+/**
+ * This function sends `buf_size` bytes from buffer `buf` to socket `sockfd`
+ * 
+ * @param sockfd - the socket to send to
+ * @param buf - the buffer to send from - must contain at least `buf_size` bytes
+ * @param buf_size - the amount of bytes to send
+ * @param flags - passed to `send`
+ * 
+ * @returns buf_size on success, -1 on error.
+ *           Failing to send exactly buf_size bytes is considered an error
+ */
+ssize_t send_all(int sockfd, const void * buf, size_t buf_size, int flags);
@@ -9,6 +9,14 @@ import sys
 import time
 import random
 import string
+import contextlib
+import json
+import errno
+import stat
+import threading
+import ctypes
+import base64
+import zlib
 
 
 def create_rust_env(verbose):
@@ -71,12 +79,13 @@ def write_tester(tester_bin, tester_env, lib):
     return run_tester
 
 
-@pytest.fixture(scope='session')
-def fuse(fuse_bin, fuse_env):
+@contextlib.contextmanager
+def mount_testfs(fuse_bin, fuse_env, extra_args=()):
     with tempfile.TemporaryDirectory() as tempdir:
         base_path = os.path.join(tempdir, "testfs")
         os.mkdir(base_path)
-        fs = subprocess.Popen([fuse_bin, base_path], env=fuse_env)
+        trace_path = os.path.join(tempdir, "trace.jsonl")
+        fs = subprocess.Popen([fuse_bin, base_path, "--trace", trace_path, *extra_args], env=fuse_env)
         time.sleep(0.1)
         yield base_path
         subprocess.run(f'fusermount -u "{base_path}"', shell=True, check=True)
@@ -86,6 +95,19 @@ def fuse(fuse_bin, fuse_env):
         print(err, file=sys.stderr)
 
 
+@pytest.fixture(scope='session')
+def fuse(fuse_bin, fuse_env):
+    with mount_testfs(fuse_bin, fuse_env) as base_path:
+        yield base_path
+
+
+def read_trace(fuse, name):
+    trace_path = os.path.join(os.path.dirname(fuse), "trace.jsonl")
+    with open(trace_path) as trace:
+        records = [json.loads(line) for line in trace]
+    return [record for record in records if record.get("name") == name]
+
+
 def const_5000(file_size):
     return min(file_size, 5000)
 
@@ -97,7 +119,8 @@ def half(file_size):
 
 @pytest.mark.parametrize(
     "path",
-    ["readempty", "readregular", "readone"] + [f"readX{i}" for i in range(2, 5)]
+    ["readempty", "readregular", "readone", "readfirstone", "readthirdshort", "readdoubling"]
+    + [f"readX{i}" for i in range(2, 5)]
 )
 @pytest.mark.parametrize(
     "count_modifier",
@@ -149,3 +172,778 @@ def test_write(fuse, write_tester, path):
 
     assert result == len(test_data)
     assert test_data == data
+
+
+@pytest.mark.parametrize("path", ["readeintr"])
+def test_read_retry(fuse, read_tester, path):
+    full_path = os.path.join(fuse, path)
+    file_size = os.stat(full_path).st_size
+
+    test_data = read_tester(full_path, None)
+    lines = test_data.stdout.splitlines()
+    result = int(lines[-1].decode())
+    extracted_test_data = b'\n'.join(lines[:-1])
+
+    assert result == file_size
+    assert extracted_test_data == b'a' * file_size
+
+
+@pytest.mark.parametrize("path", ["readeio"])
+def test_read_error(fuse, read_tester, path):
+    full_path = os.path.join(fuse, path)
+
+    test_data = read_tester(full_path, None)
+    result = int(test_data.stdout.splitlines()[-1].decode())
+
+    assert result == -1
+
+
+@pytest.mark.parametrize("path", ["writeeintr"])
+def test_write_retry(fuse, write_tester, path):
+    full_path = os.path.join(fuse, path)
+
+    test_data = ''.join(random.choice(string.ascii_letters) for _ in range(10_000))
+
+    result = write_tester(full_path, test_data)
+    result = int(result.stdout.decode())
+
+    with open(full_path, 'r') as reader:
+        data = reader.read()
+
+    # truncate the file
+    with open(full_path, 'w'):
+        pass
+
+    assert result == len(test_data)
+    assert test_data == data
+
+
+@pytest.mark.parametrize("path", ["writeenospc", "writeeio"])
+def test_write_error(fuse, write_tester, path):
+    full_path = os.path.join(fuse, path)
+
+    test_data = ''.join(random.choice(string.ascii_letters) for _ in range(10_000))
+
+    result = write_tester(full_path, test_data)
+    result = int(result.stdout.decode())
+
+    # truncate the file
+    with open(full_path, 'w'):
+        pass
+
+    assert result == -1
+
+
+def test_handles_are_independent(fuse):
+    full_path = os.path.join(fuse, "readfirstone")
+
+    first = os.open(full_path, os.O_RDONLY)
+    second = os.open(full_path, os.O_RDONLY)
+    try:
+        # every handle gets its own short first read
+        assert len(os.read(first, 100)) == 1
+        assert len(os.read(second, 100)) == 1
+        assert len(os.read(first, 100)) == 100
+        assert len(os.read(second, 100)) == 100
+    finally:
+        os.close(first)
+        os.close(second)
+
+
+def test_scenario_file(fuse_bin, fuse_env, read_tester, tmp_path):
+    scenario = tmp_path / "scenario.toml"
+    scenario.write_text("""
+[[file]]
+name = "custom"
+content = { repeat = "xyz", count = 1000 }
+perms = 0o644
+read = { kind = "sequence", sizes = [7, 1, 3], repeat = true }
+""")
+    with mount_testfs(fuse_bin, fuse_env, ["--scenario", str(scenario)]) as base_path:
+        full_path = os.path.join(base_path, "custom")
+        assert os.listdir(base_path) == ["custom"]
+        assert os.stat(full_path).st_mode & 0o777 == 0o644
+
+        test_data = read_tester(full_path, None)
+        lines = test_data.stdout.splitlines()
+        assert int(lines[-1].decode()) == 3000
+        assert b'\n'.join(lines[:-1]) == b"xyz" * 1000
+
+
+def control(fuse, *commands):
+    fd = os.open(os.path.join(fuse, ".control"), os.O_RDWR)
+    try:
+        os.write(fd, ''.join(command + '\n' for command in commands).encode())
+        return os.read(fd, 1 << 16).decode().splitlines()
+    finally:
+        os.close(fd)
+
+
+def test_control(fuse, read_tester):
+    spec = '{"name": "controlled", "content": "0123456789", "read": {"kind": "constant", "size": 3}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        full_path = os.path.join(fuse, "controlled")
+        test_data = read_tester(full_path, None)
+        lines = test_data.stdout.splitlines()
+        assert int(lines[-1].decode()) == 10
+        assert lines[0] == b"0123456789"
+
+        [response] = control(fuse, "stats controlled")
+        assert response.startswith("ok ")
+        stats = json.loads(response[len("ok "):])
+        # at least 4 short reads
+        assert stats["read_calls"] >= 4
+        assert stats["bytes_read"] == 10
+
+        spec = '{"name": "controlled", "content": "abc"}'
+        assert control(fuse, f"replace {spec}") == ["ok"]
+        with open(full_path, 'rb') as reader:
+            assert reader.read() == b"abc"
+    finally:
+        assert control(fuse, "remove controlled") == ["ok"]
+    assert not os.path.exists(os.path.join(fuse, "controlled"))
+    assert control(fuse, "remove controlled")[0].startswith("error")
+
+    # a policy that transfers nothing would stall the caller
+    spec = '{"name": "stalled", "read": {"kind": "constant", "size": 0}}'
+    [response] = control(fuse, f"add {spec}")
+    assert response.startswith("error") and "size must be positive" in response
+
+
+def test_trace(fuse, read_tester):
+    spec = '{"name": "traced", "content": {"repeat": "a", "count": 10000}, "read": {"kind": "divisor", "divisor": 3}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        test_data = read_tester(os.path.join(fuse, "traced"), None)
+        assert int(test_data.stdout.splitlines()[-1].decode()) == 10000
+    finally:
+        assert control(fuse, "remove traced") == ["ok"]
+
+    reads = [record for record in read_trace(fuse, "traced") if record["op"] == "read"]
+    assert reads
+    # the library never re-reads an offset, nor skips one
+    expected_offset = 0
+    for record in reads:
+        assert record["offset"] == expected_offset
+        expected_offset += record["result"]
+    assert expected_offset == 10000
+
+
+def test_stats_xattrs(fuse, read_tester):
+    spec = '{"name": "counted", "content": {"repeat": "a", "count": 1000}, "read": {"kind": "constant", "size": 100}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        full_path = os.path.join(fuse, "counted")
+        assert "user.testio.read_calls" in os.listxattr(full_path)
+
+        read_tester(full_path, None)
+        assert int(os.getxattr(full_path, "user.testio.bytes_read")) == 1000
+        assert int(os.getxattr(full_path, "user.testio.read_calls")) >= 10
+        assert int(os.getxattr(full_path, "user.testio.max_request")) <= 1000
+        assert int(os.getxattr(full_path, "user.testio.errors_injected")) == 0
+        assert int(os.getxattr(full_path, "user.testio.noncontiguous_calls")) == 0
+
+        os.setxattr(full_path, "user.testio.reset", b"1")
+        assert int(os.getxattr(full_path, "user.testio.read_calls")) == 0
+    finally:
+        assert control(fuse, "remove counted") == ["ok"]
+
+
+def test_seek_data_hole(fuse):
+    fd = os.open(os.path.join(fuse, "sparse"), os.O_RDONLY)
+    try:
+        assert os.lseek(fd, 0, os.SEEK_DATA) == 4096
+        assert os.lseek(fd, 4096, os.SEEK_HOLE) == 8192
+        assert os.lseek(fd, 8192, os.SEEK_DATA) == 12288
+        with pytest.raises(OSError) as error:
+            os.lseek(fd, 16384, os.SEEK_DATA)
+        assert error.value.errno == errno.ENXIO
+        assert os.pread(fd, 8, 4092) == b"\0" * 4 + b"a" * 4
+    finally:
+        os.close(fd)
+
+    spec = '{"name": "sparsepast", "content": "0123456789", "holes": [[4, 20]]}'
+    assert control(fuse, f"add {spec}")[0].startswith("error")
+
+    assert control(fuse, 'add {"name": "sparsewrite", "writable": true}') == ["ok"]
+    try:
+        fd = os.open(os.path.join(fuse, "sparsewrite"), os.O_RDWR)
+        try:
+            os.pwrite(fd, b"data", 8192)
+            assert os.lseek(fd, 0, os.SEEK_DATA) == 8192
+            assert os.lseek(fd, 0, os.SEEK_HOLE) == 0
+            assert os.lseek(fd, 8192, os.SEEK_HOLE) == 8196
+        finally:
+            os.close(fd)
+    finally:
+        assert control(fuse, "remove sparsewrite") == ["ok"]
+
+
+def test_subdirectories(fuse, read_tester):
+    spec = '{"name": "grouped/short/X3", "content": {"repeat": "a", "count": 3000}, "read": {"kind": "divisor", "divisor": 3}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        assert "grouped" in os.listdir(fuse)
+        assert os.listdir(os.path.join(fuse, "grouped")) == ["short"]
+        assert os.listdir(os.path.join(fuse, "grouped", "short")) == ["X3"]
+        assert "grouped/short/X3" in json.loads(control(fuse, "list")[0][len("ok "):])
+
+        test_data = read_tester(os.path.join(fuse, "grouped", "short", "X3"), None)
+        lines = test_data.stdout.splitlines()
+        assert int(lines[-1].decode()) == 3000
+
+        os.mkdir(os.path.join(fuse, "grouped", "empty"))
+        assert sorted(os.listdir(os.path.join(fuse, "grouped"))) == ["empty", "short"]
+        with pytest.raises(OSError) as error:
+            os.rmdir(os.path.join(fuse, "grouped", "short"))
+        assert error.value.errno == errno.ENOTEMPTY
+        os.rmdir(os.path.join(fuse, "grouped", "empty"))
+        assert os.listdir(os.path.join(fuse, "grouped")) == ["short"]
+        assert [record["op"] for record in read_trace(fuse, "grouped/empty")] == ["mkdir", "rmdir"]
+        assert [record["errno"] for record in read_trace(fuse, "grouped/short")] == [errno.ENOTEMPTY]
+    finally:
+        assert control(fuse, "remove grouped/short/X3") == ["ok"]
+        # the directories created for the file stay behind it
+        os.rmdir(os.path.join(fuse, "grouped", "short"))
+        os.rmdir(os.path.join(fuse, "grouped"))
+    assert "grouped" not in os.listdir(fuse)
+
+
+def umask():
+    mask = os.umask(0)
+    os.umask(mask)
+    return mask
+
+
+def test_create_rename_unlink(fuse):
+    assert control(fuse, 'template {"write": {"kind": "constant", "size": 100}}') == ["ok"]
+    try:
+        temp_path = os.path.join(fuse, "atomic.tmp")
+        final_path = os.path.join(fuse, "atomic")
+        data = b"x" * 1000
+
+        fd = os.open(temp_path, os.O_CREAT | os.O_WRONLY, 0o644)
+        try:
+            # new files take the write policy of the template
+            assert os.write(fd, data) == 100
+            written = 100
+            while written < len(data):
+                written += os.write(fd, data[written:])
+        finally:
+            os.close(fd)
+        # the mode passed to open, without the umask
+        assert stat.S_IMODE(os.stat(temp_path).st_mode) == 0o644 & ~umask()
+
+        os.rename(temp_path, final_path)
+        assert "atomic" in os.listdir(fuse)
+        assert "atomic.tmp" not in os.listdir(fuse)
+        with open(final_path, "rb") as reader:
+            assert reader.read() == data
+
+        # an unlinked file stays readable through handles that are already open
+        fd = os.open(final_path, os.O_RDONLY)
+        try:
+            os.unlink(final_path)
+            assert "atomic" not in os.listdir(fuse)
+            assert os.pread(fd, 10, 0) == b"x" * 10
+        finally:
+            os.close(fd)
+    finally:
+        assert control(fuse, "template {}") == ["ok"]
+
+    # the perms of the template override the mode
+    assert control(fuse, 'template {"perms": 420}') == ["ok"]
+    try:
+        private_path = os.path.join(fuse, "private")
+        os.close(os.open(private_path, os.O_CREAT | os.O_WRONLY, 0o600))
+        assert stat.S_IMODE(os.stat(private_path).st_mode) == 0o644
+        os.unlink(private_path)
+    finally:
+        assert control(fuse, "template {}") == ["ok"]
+
+
+def test_latency(fuse, read_tester):
+    full_path = os.path.join(fuse, "readslow")
+    start = time.monotonic()
+    test_data = read_tester(full_path, None)
+    elapsed = time.monotonic() - start
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == 1000
+    # 10 reads of 100 bytes, each delayed by 20ms
+    assert elapsed >= 0.2
+
+
+def test_blocked_file(fuse):
+    result = {}
+
+    def blocked_read():
+        # a single read call - a buffered reader would make a second, held call at the end of the file
+        fd = os.open(os.path.join(fuse, "readblock"), os.O_RDONLY)
+        try:
+            result["data"] = os.read(fd, 100)
+        finally:
+            os.close(fd)
+
+    reader = threading.Thread(target=blocked_read)
+    reader.start()
+    try:
+        time.sleep(0.3)
+        assert reader.is_alive()
+        # other files aren't stalled by the blocked read
+        with open(os.path.join(fuse, "readregular"), "rb") as other:
+            assert other.read() == b"readregular\n"
+    finally:
+        assert control(fuse, "unblock readblock") == ["ok 1"]
+        reader.join(timeout=5)
+    assert result["data"] == b"released\n"
+
+
+def test_removed_blocked_file(fuse):
+    spec = '{"name": "removedblock", "content": "held", "latency": {"kind": "block"}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    full_path = os.path.join(fuse, "removedblock")
+    result = {}
+
+    def blocked_reads():
+        fd = os.open(full_path, os.O_RDONLY)
+        try:
+            for attempt in ("held", "orphan"):
+                try:
+                    os.pread(fd, 100, 0)
+                except OSError as error:
+                    result[attempt] = error.errno
+        finally:
+            os.close(fd)
+
+    reader = threading.Thread(target=blocked_reads)
+    reader.start()
+    try:
+        time.sleep(0.3)
+        assert reader.is_alive()
+    finally:
+        # nothing can unblock the file once it's unlinked, so the held read fails, and so do the reads that follow
+        os.unlink(full_path)
+        reader.join(timeout=5)
+    assert not reader.is_alive()
+    assert result == {"held": errno.EIO, "orphan": errno.EIO}
+
+
+def test_blocked_write_applied_once_unblocked(fuse):
+    spec = '{"name": "heldwrite", "writable": true, "latency": {"kind": "block"}}'
+    assert control(fuse, f"add {spec}") == ["ok"]
+    full_path = os.path.join(fuse, "heldwrite")
+    try:
+        fd = os.open(full_path, os.O_WRONLY)
+        writer = threading.Thread(target=os.pwrite, args=(fd, b"held", 0))
+        writer.start()
+        try:
+            time.sleep(0.3)
+            assert writer.is_alive()
+            # the held call hasn't reached the file yet
+            [response] = control(fuse, "stats heldwrite")
+            stats = json.loads(response[len("ok "):])
+            assert stats["write_calls"] == 0
+            assert stats["bytes_written"] == 0
+        finally:
+            assert control(fuse, "unblock heldwrite") == ["ok 1"]
+            writer.join(timeout=5)
+            os.close(fd)
+        [response] = control(fuse, "stats heldwrite")
+        stats = json.loads(response[len("ok "):])
+        assert stats["write_calls"] == 1
+        assert stats["bytes_written"] == 4
+    finally:
+        assert control(fuse, "remove heldwrite") == ["ok"]
+
+
+def test_interrupted_read(fuse, tester_bin, tester_env, lib):
+    full_path = os.path.join(fuse, "readinterruptible")
+    os.setxattr(full_path, "user.testio.reset", b"1")
+    # every read takes 50ms, so an interval longer than that lets the reads that aren't interrupted complete
+    test_data = subprocess.run(
+        [tester_bin, "--interrupt-us=75000", lib, full_path, "read"],
+        env=tester_env,
+        capture_output=True,
+        check=True,
+        timeout=30,
+    )
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == 1000
+    assert b'\n'.join(lines[:-1]) == b"a" * 1000
+    interrupts = int(test_data.stderr.decode().split("interrupts: ")[1].split()[0])
+    assert interrupts > 0
+    # the interrupted reads failed with EINTR before reaching the file, and were retried
+    assert int(os.getxattr(full_path, "user.testio.errors_injected")) > 0
+    assert int(os.getxattr(full_path, "user.testio.bytes_read")) == 1000
+
+
+def run_peer_tester(tester_bin, tester_env, lib, file_path, *args):
+    return subprocess.run(
+        [tester_bin, lib, file_path, *args],
+        env=tester_env,
+        capture_output=True,
+        timeout=30,
+    )
+
+
+def test_recv_fragments(tester_bin, tester_env, lib, tmp_path):
+    data = b"fragmented data " * 100
+    source = tmp_path / "source"
+    source.write_bytes(data)
+
+    for policy in ['{"kind": "constant", "size": 1}', '{"kind": "sequence", "sizes": [1, 7, 0], "repeat": true}']:
+        test_data = run_peer_tester(tester_bin, tester_env, lib, source, "recv", f"--policy={policy}")
+        assert test_data.returncode == 0
+        lines = test_data.stdout.splitlines()
+        assert int(lines[-1].decode()) == len(data)
+        assert b'\n'.join(lines[:-1]) == data
+
+    # the peer resets the connection before all the data arrives
+    test_data = run_peer_tester(tester_bin, tester_env, lib, source, "recv", "--reset-after=100")
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == -1
+
+
+def test_send_fragments(tester_bin, tester_env, lib, tmp_path):
+    data = "x" * 100000
+    sink = tmp_path / "sink"
+
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, sink, "send", data, "--sndbuf=1024", '--policy={"kind": "constant", "size": 100}'
+    )
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == len(data)
+    assert sink.read_bytes() == data.encode()
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, sink, "send", data, "--sndbuf=1024", "--reset-after=1000")
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == -1
+    assert sink.read_bytes() == data[:1000].encode()
+
+
+def test_read_pipe(tester_bin, tester_env, lib, tmp_path):
+    data = b"piped data " * 1000
+    source = tmp_path / "source"
+    source.write_bytes(data)
+
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, source, "read", "--source=pipe", '--policy={"kind": "constant", "size": 10}'
+    )
+    assert test_data.returncode == 0
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == len(data)
+    assert b'\n'.join(lines[:-1]) == data
+
+    # policies are validated like the policies of a scenario
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, source, "read", "--source=pipe", '--policy={"kind": "divisor", "divisor": 0}'
+    )
+    assert test_data.returncode != 0
+    assert b"divisor must be positive" in test_data.stderr
+
+
+def test_write_pipe(tester_bin, tester_env, lib, tmp_path):
+    data = "y" * 100000
+    sink = tmp_path / "sink"
+
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, sink, "write", data, "--sink=pipe", '--policy={"kind": "constant", "size": 100}'
+    )
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == len(data)
+    assert sink.read_bytes() == data.encode()
+
+    # the reader closes the pipe midway - the library gets EPIPE
+    test_data = run_peer_tester(tester_bin, tester_env, lib, sink, "write", data, "--sink=pipe", "--reset-after=1000")
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == -1
+    assert sink.read_bytes() == data[:1000].encode()
+
+
+def test_nonblocking(fuse, tester_bin, tester_env, lib):
+    # blocking handles are served by the file as usual
+    full_path = os.path.join(fuse, "readnonblock")
+    run_peer_tester(tester_bin, tester_env, lib, full_path, "read")
+    assert int(os.getxattr(full_path, "user.testio.errors_injected")) == 0
+    os.setxattr(full_path, "user.testio.reset", b"1")
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--nonblock", "read")
+    assert test_data.returncode == 0
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == 10000
+    assert b'\n'.join(lines[:-1]) == b"a" * 10000
+    # 2 EAGAIN failures before every served call
+    read_calls = int(os.getxattr(full_path, "user.testio.read_calls"))
+    assert int(os.getxattr(full_path, "user.testio.errors_injected")) == read_calls - read_calls // 3
+
+    full_path = os.path.join(fuse, "writenonblock")
+    data = "b" * 10000
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--nonblock", "write", data)
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == len(data)
+    assert int(os.getxattr(full_path, "user.testio.errors_injected")) > 0
+    with open(full_path, 'r') as reader:
+        assert reader.read() == data
+
+
+def test_nonblocking_script(fuse):
+    spec = ('{"name": "nonblockscript", "content": "0123456789", "read": {"kind": "errno", "script": "ok 3, EIO"}, '
+            '"eagain": {"kind": "alternate", "failures": 1}}')
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        fd = os.open(os.path.join(fuse, "nonblockscript"), os.O_RDONLY | os.O_NONBLOCK)
+        try:
+            # the calls that would block don't reach the file, so they don't consume the script
+            with pytest.raises(BlockingIOError):
+                os.pread(fd, 100, 0)
+            assert os.pread(fd, 100, 0) == b"012"
+            with pytest.raises(BlockingIOError):
+                os.pread(fd, 100, 3)
+            with pytest.raises(OSError) as error:
+                os.pread(fd, 100, 3)
+            assert error.value.errno == errno.EIO
+        finally:
+            os.close(fd)
+    finally:
+        assert control(fuse, "remove nonblockscript") == ["ok"]
+
+
+def test_symbol_signatures(fuse, tester_bin, tester_env, lib):
+    library = ctypes.CDLL(os.path.abspath(lib))
+    if not hasattr(library, "read_all_status") or not hasattr(library, "write_all_bool"):
+        pytest.skip("the library doesn't export read_all_status / write_all_bool")
+
+    full_path = os.path.join(fuse, "readX3")
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, full_path, "--read-symbol=read_all_status", "--read-signature=status", "read"
+    )
+    assert test_data.returncode == 0
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == 100000
+    assert b'\n'.join(lines[:-1]) == b"a" * 100000
+
+    full_path = os.path.join(fuse, "writeX3")
+    data = "c" * 10000
+    test_data = run_peer_tester(
+        tester_bin, tester_env, lib, full_path, "--write-symbol=write_all_bool", "--write-signature=bool", "write", data
+    )
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.splitlines()[-1].decode()) == len(data)
+    with open(full_path, 'r') as reader:
+        assert reader.read() == data
+    with open(full_path, 'w'):
+        pass
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--read-symbol=missing_read_all", "read")
+    assert test_data.returncode != 0
+
+
+def test_binary_data(fuse, tester_bin, tester_env, lib, tmp_path):
+    data = bytes(range(256)) * 40
+    full_path = os.path.join(fuse, "writeX2")
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--encoding=hex", "write", data.hex())
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.decode()) == len(data)
+    with open(full_path, 'rb') as reader:
+        assert reader.read() == data
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--encoding=base64", "read")
+    assert test_data.returncode == 0
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == len(data)
+    assert base64.b64decode(lines[0]) == data
+
+    out_file = tmp_path / "out"
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, f"--out-file={out_file}", "read")
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.decode()) == len(data)
+    assert out_file.read_bytes() == data
+
+    with open(full_path, 'w'):
+        pass
+    data_file = tmp_path / "data"
+    data_file.write_bytes(b"\0" * 1000)
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "write", f"--data-file={data_file}")
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.decode()) == 1000
+    with open(full_path, 'rb') as reader:
+        assert reader.read() == b"\0" * 1000
+    with open(full_path, 'w'):
+        pass
+
+
+def test_json_output(fuse, tester_bin, tester_env, lib):
+    full_path = os.path.join(fuse, "readX3")
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--output=json", "read")
+    assert test_data.returncode == 0
+    report = json.loads(test_data.stdout)
+    assert report["result"] == 100000
+    assert report["bytes"] == 100000
+    assert report["errno"] == 0
+    assert report["crc32"] == zlib.crc32(b"a" * 100000)
+    # short reads - the library must have called read more than once
+    assert report["syscalls"] > 1
+    assert report["elapsed"] > 0
+
+    full_path = os.path.join(fuse, "readeio")
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--output=json", "read")
+    assert test_data.returncode == 0
+    report = json.loads(test_data.stdout)
+    assert report["result"] == -1
+    assert report["errno"] == errno.EIO
+
+
+def test_error_capture(fuse, tester_bin, tester_env, lib):
+    full_path = os.path.join(fuse, "writeenospc")
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--output=json", "write", "d" * 1000)
+    assert test_data.returncode == 0
+    report = json.loads(test_data.stdout)
+    assert report["result"] == -1
+    assert report["errno"] == errno.ENOSPC
+    # the perror() of the library, captured while it ran
+    assert f"write: {os.strerror(errno.ENOSPC)}" in report["stderr"]
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "write", "d" * 1000)
+    assert test_data.returncode == 0
+    assert int(test_data.stdout.decode()) == -1
+    assert f"errno: {errno.ENOSPC}" in test_data.stderr.decode()
+    with open(full_path, 'w'):
+        pass
+
+
+def test_native_runner(fuse_bin, fuse_env, lib):
+    test_data = subprocess.run([fuse_bin, "run", "--lib", lib], env=fuse_env, capture_output=True, timeout=120)
+    assert test_data.returncode == 0, test_data.stdout.decode()
+    assert test_data.stdout.splitlines()[-1].decode().endswith(" 0 failed")
+
+
+def test_composed_behaviors(fuse, tester_bin, tester_env, lib):
+    # a writable file with short reads, short writes and an EIO on the third write
+    full_path = os.path.join(fuse, "readwriteshort")
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "read")
+    assert test_data.returncode == 0
+    lines = test_data.stdout.splitlines()
+    assert int(lines[-1].decode()) == 1000
+    assert b'\n'.join(lines[:-1]) == b"a" * 1000
+    assert int(os.getxattr(full_path, "user.testio.read_calls")) > 2
+
+    test_data = run_peer_tester(tester_bin, tester_env, lib, full_path, "--output=json", "write", "a" * 1000)
+    assert test_data.returncode == 0
+    report = json.loads(test_data.stdout)
+    assert report["result"] == -1
+    assert report["errno"] == errno.EIO
+    with open(full_path, 'rb') as reader:
+        assert reader.read() == b"a" * 1000
+
+
+def test_random_seeds(fuse, read_tester, write_tester):
+    full_path = os.path.join(fuse, "readrandom")
+    assert "user.testio.seed" in os.listxattr(full_path)
+    assert int(os.getxattr(full_path, "user.testio.seed")) == 1
+    file_size = os.stat(full_path).st_size
+    for seed in range(50):
+        os.setxattr(full_path, "user.testio.seed", str(seed).encode())
+        test_data = read_tester(full_path, None)
+        lines = test_data.stdout.splitlines()
+        assert int(lines[-1].decode()) == file_size, f"seed {seed}"
+        assert b'\n'.join(lines[:-1]) == b"a" * file_size, f"seed {seed}"
+    assert int(os.getxattr(full_path, "user.testio.seed")) == 49
+    # the trace records the seed of every call
+    reads = [record for record in read_trace(fuse, "readrandom") if record["op"] == "read"]
+    assert reads and reads[-1]["seed"] == 49
+
+    full_path = os.path.join(fuse, "writerandom")
+    for seed in range(50):
+        os.setxattr(full_path, "user.testio.seed", str(seed).encode())
+        test_data = ''.join(random.choice(string.ascii_letters) for _ in range(10_000))
+        result = int(write_tester(full_path, test_data).stdout.decode())
+        with open(full_path, 'r') as reader:
+            data = reader.read()
+        # truncate the file
+        with open(full_path, 'w'):
+            pass
+        assert result == len(test_data), f"seed {seed}"
+        assert data == test_data, f"seed {seed}"
+
+    # files without randomness have no seed
+    with pytest.raises(OSError):
+        os.setxattr(os.path.join(fuse, "readone"), "user.testio.seed", b"1")
+
+
+def test_mixed_seeds(fuse):
+    spec = ('{"name": "mixedseeds", "content": "0123456789", "read": {"kind": "random", "seed": 3, "min": 1, "max": 50}, '
+            '"write": {"kind": "random", "seed": 7, "min": 1, "max": 50}}')
+    assert control(fuse, f"add {spec}") == ["ok"]
+    try:
+        full_path = os.path.join(fuse, "mixedseeds")
+        # the file reports the seed of its first random part, and every part is seeded with it
+        assert int(os.getxattr(full_path, "user.testio.seed")) == 3
+        fd = os.open(full_path, os.O_WRONLY)
+        try:
+            written = os.pwrite(fd, b"x" * 100, 0)
+        finally:
+            os.close(fd)
+
+        os.setxattr(full_path, "user.testio.seed", b"3")
+        fd = os.open(full_path, os.O_WRONLY)
+        try:
+            assert os.pwrite(fd, b"x" * 100, 0) == written
+        finally:
+            os.close(fd)
+    finally:
+        assert control(fuse, "remove mixedseeds") == ["ok"]
+
+
+def test_fuzz(fuse_bin, fuse_env, lib, tmp_path):
+    test_data = subprocess.run(
+        [fuse_bin, "fuzz", "--lib", lib, "--iterations", "200"], env=fuse_env, capture_output=True, timeout=120
+    )
+    assert test_data.returncode == 0, test_data.stdout.decode()
+    assert test_data.stdout.splitlines()[-1].decode() == "200 passed, 0 failed"
+
+    # read_all of 3 bytes, served a byte at a time and then failing with EIO (kind 1)
+    failing = tmp_path / "failing"
+    failing.write_bytes(bytes([0, 2, 100, 0, 0, 1, 0, 0, 128]) + b"abc")
+    # write_all of 3 bytes, interrupted once (kind 0)
+    interrupted = tmp_path / "interrupted"
+    interrupted.write_bytes(bytes([1, 1, 0, 0, 0, 0]) + b"abc")
+    test_data = subprocess.run(
+        [fuse_bin, "fuzz", "--lib", lib, str(failing), str(interrupted)], env=fuse_env, capture_output=True, timeout=30
+    )
+    assert test_data.returncode == 0, test_data.stdout.decode()
+    assert test_data.stdout.splitlines()[-1].decode() == "2 passed, 0 failed"
+
+
+def test_expected_writes(fuse, write_tester):
+    full_path = os.path.join(fuse, "writeexpected")
+    payload = "0123456789" * 1000
+    result = write_tester(full_path, payload)
+    assert int(result.stdout.decode()) == len(payload)
+    for counter in ["rewrites", "skips", "divergent_writes"]:
+        assert int(os.getxattr(full_path, f"user.testio.{counter}")) == 0
+    assert "user.testio.first_divergence" not in os.listxattr(full_path)
+
+    # truncating starts over - then a buggy library resends a chunk with the wrong offset
+    with open(full_path, 'w'):
+        pass
+    os.setxattr(full_path, "user.testio.reset", b"1")
+    fd = os.open(full_path, os.O_WRONLY)
+    try:
+        # the file serves a third of every write
+        assert os.pwrite(fd, payload[:99].encode(), 0) == 33
+        assert os.pwrite(fd, payload[100:199].encode(), 25) == 33
+        assert os.pwrite(fd, payload[300:399].encode(), 300) == 33
+    finally:
+        os.close(fd)
+    with open(full_path, 'w'):
+        pass
+    assert int(os.getxattr(full_path, "user.testio.rewrites")) == 1
+    assert int(os.getxattr(full_path, "user.testio.skips")) == 1
+    assert int(os.getxattr(full_path, "user.testio.divergent_writes")) == 1
+    assert int(os.getxattr(full_path, "user.testio.first_divergence")) == 25
+
+    checks = [record["check"] for record in read_trace(fuse, "writeexpected") if record["op"] == "write"]
+    assert any(check["rewrite"] and check.get("divergence") == 25 for check in checks)
+    assert any(check["skip"] for check in checks)
@@ -0,0 +1,41 @@
+/*
+ * Reads and writes the files of the built-in scenario through `harness::mount_default()`,
+ * the way the tests of a Rust I/O helper would.
+ */
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+use testio::harness;
+
+// the value of the stat `name` of the file `file`, from the control channel
+fn stat(mount: &harness::Mount, file: &str, name: &str) -> u64 {
+    let response = mount.control(&format!("stats {}", file)).expect("Failed to query the stats");
+    let stats: serde_json::Value = serde_json::from_str(response.strip_prefix("ok ").expect("The stats command failed"))
+        .expect("Failed to parse the stats");
+    stats[name].as_u64().expect("The stat is missing")
+}
+
+#[test]
+fn short_reads() {
+    let mount = harness::mount_default().expect("Failed to mount the built-in scenario");
+    let mut data = Vec::new();
+    File::open(mount.file("readX3")).unwrap().read_to_end(&mut data).unwrap();
+    assert_eq!(data, vec![b'a'; 100000]);
+    // every call was cut to a third of the request
+    assert!(stat(&mount, "readX3", "read_calls") > 3);
+}
+
+#[test]
+fn short_writes() {
+    let mount = harness::mount_default().expect("Failed to mount the built-in scenario");
+    let path = mount.file("writeX3");
+    let data: Vec<u8> = (0..10000).map(|index| (index % 251) as u8).collect();
+    File::options().write(true).open(&path).unwrap().write_all(&data).unwrap();
+    assert!(stat(&mount, "writeX3", "write_calls") > 1);
+    assert_eq!(stat(&mount, "writeX3", "bytes_written"), data.len() as u64);
+
+    let mut written = Vec::new();
+    File::open(&path).unwrap().read_to_end(&mut written).unwrap();
+    assert_eq!(written, data);
+}